@@ -0,0 +1,55 @@
+//! Run with `cargo run --example async_capture --features async`.
+extern crate redpitaya_mercury;
+#[cfg(feature = "async")]
+extern crate tokio;
+
+#[cfg(feature = "async")]
+use redpitaya_mercury::async_irq::AsyncIrq;
+#[cfg(feature = "async")]
+use redpitaya_mercury::prelude::*;
+#[cfg(feature = "async")]
+use redpitaya_mercury::la;
+#[cfg(feature = "async")]
+use tokio::runtime::Builder;
+
+#[cfg(not(feature = "async"))]
+fn main() {
+    eprintln!("async_capture needs the `async` feature");
+}
+
+#[cfg(feature = "async")]
+fn main() {
+    let rt = Builder::new_current_thread().enable_io().build().unwrap();
+    let _guard = rt.enter();
+
+    // setup logic analyzer
+    let mut la = LogicAnalyzer::new().unwrap();
+    la.default();
+    la.set_input_mask(0xffff);
+    la.set_trigger_pre(la::BUFFER_SIZE / 2);
+    la.set_trigger_post(la::BUFFER_SIZE / 2);
+    la.set_decimation(1000);
+    la.set_sync_source(SyncSource::La);
+    la.set_trigger_source(TriggerSource::La);
+
+    // wait for the ps2pl interrupt on the runtime instead of blocking in capture
+    let mut irq = AsyncIrq::new(la.take_irq().unwrap()).unwrap();
+
+    // reset and start
+    la.reset();
+    la.start_trigger();
+
+    loop {
+        irq.arm().unwrap();
+        if !la.is_running() {
+            break;
+        }
+        let count = rt.block_on(irq.wait_irq()).unwrap();
+        println!("Received interrupt {}", count);
+    }
+
+    // print data
+    for sample in la.data(la.available()) {
+        println!("{:016b}", sample);
+    }
+}
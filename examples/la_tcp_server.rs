@@ -2,9 +2,11 @@ extern crate redpitaya_mercury;
 
 use redpitaya_mercury::prelude::*;
 use redpitaya_mercury::la;
+use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 use std::net::{TcpStream, TcpListener};
+use std::time::Duration;
 
 // Max Ethernet frame size: 1500 bytes
 // Max TCP payload: 1460 bytes (- 20 bytes IP header - 20 bytes TCP header)
@@ -15,6 +17,11 @@ use std::net::{TcpStream, TcpListener};
 // Sample rate: 125MSps / 10000 ~ 12.5kSps
 // Buffer fills ~1/sec
 // Takes 6 tcp packets to send a buffer
+//
+// Usage: la_tcp_server [TIMEOUT]
+//
+// An acquisition taking longer than TIMEOUT seconds (default 5) is
+// restarted.
 
 enum CommandKind {
     HwId = 0,
@@ -58,8 +65,8 @@ impl Command {
     }
 }
 
-fn logic_analyzer() -> LogicAnalyzer {
-    let mut la = LogicAnalyzer::new();
+fn logic_analyzer() -> Result<LogicAnalyzer, Error> {
+    let mut la = LogicAnalyzer::new()?;
     la.default();
     la.set_input_mask(0xffff);
     la.set_input_polarity(0xffff);
@@ -68,29 +75,31 @@ fn logic_analyzer() -> LogicAnalyzer {
     la.set_decimation(10000);
     la.set_sync_source(SyncSource::La);
     la.set_trigger_source(TriggerSource::La);
-    la
+    Ok(la)
 }
 
-fn la_write_buffer(la: &mut LogicAnalyzer, stream: &mut TcpStream) {
+fn la_write_buffer(la: &mut LogicAnalyzer, stream: &mut TcpStream, timeout: Duration) {
     let mut tcp_buf = [0u8; la::BUFFER_SIZE as usize];
 
     loop {
-        // reset and start
-        la.reset();
-        la.start_trigger();
-
-        // reset and start
-        la.reset();
-        la.start_trigger();
-
-        // wait for data
-        while la.is_running() {}
+        // start and wait for data
+        let data = match la.capture_now(timeout) {
+            Ok(data) => data,
+            Err(Error::Timeout(_)) => {
+                println!("Acquisition timed out, restarting");
+                continue;
+            }
+            Err(e) => {
+                println!("Acquisition failed: {}", e);
+                return;
+            }
+        };
 
         println!("Sending buffer");
         // print data
         let mut len = 0;
-        for (i, sample) in la.buffer().iter().enumerate() {
-            tcp_buf[i] = sample.read() as u8;
+        for (i, sample) in data.into_iter().enumerate() {
+            tcp_buf[i] = sample as u8;
             len = i;
         }
         if stream.write(&tcp_buf[0..len]).is_err() {
@@ -111,11 +120,26 @@ fn get_hwid() -> String {
 }
 
 fn main() {
+    let timeout = match env::args().nth(1).map(|arg| arg.parse::<f32>()) {
+        Some(Ok(secs)) if secs > 0.0 => Duration::from_secs_f32(secs),
+        Some(_) => {
+            println!("Invalid timeout");
+            return;
+        }
+        None => Duration::from_secs(5),
+    };
+
     let hwid = get_hwid();
     println!("{}", hwid);
 
     let listener = TcpListener::bind("0.0.0.0:5555").unwrap();
-    let mut la = logic_analyzer();
+    let mut la = match logic_analyzer() {
+        Ok(la) => la,
+        Err(e) => {
+            println!("Unable to open logic analyzer: {}", e);
+            return;
+        }
+    };
 
     println!("Listening on 0.0.0.0:5555");
 
@@ -139,7 +163,7 @@ fn main() {
                     },
                     CommandKind::AcquisitionStart => {
                         println!("Received AcquisitionStart");
-                        la_write_buffer(&mut la, &mut stream);
+                        la_write_buffer(&mut la, &mut stream, timeout);
                     },
                     CommandKind::AcquisitionStop => {
                         println!("Received AcquisitionStop");
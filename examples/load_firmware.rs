@@ -0,0 +1,8 @@
+extern crate redpitaya_mercury;
+
+use redpitaya_mercury::prelude::*;
+
+fn main() {
+    let hwid = Firmware::new().load().unwrap();
+    println!("loaded mercury firmware, hwid = {:x}", hwid);
+}
@@ -5,11 +5,11 @@ use redpitaya_mercury::prelude::*;
 use redpitaya_mercury::la;
 
 fn main() {
-    let hwid = HwId::new();
+    let hwid = HwId::new().unwrap();
     hwid.show();
 
     // setup logic analyzer
-    let mut la = LogicAnalyzer::new();
+    let mut la = LogicAnalyzer::new().unwrap();
     la.default();
     la.set_input_mask(0xffff);
     la.set_trigger_pre(la::BUFFER_SIZE / 2);
@@ -21,15 +21,11 @@ fn main() {
 
     thread::sleep(time::Duration::from_millis(1000));
 
-    // reset and start
-    la.reset();
-    la.start_trigger();
-
-    // wait for data
-    while la.is_running() {}
+    // start and wait for data
+    let data = la.capture_now(time::Duration::from_secs(5)).unwrap();
 
     // print data
-    for sample in la.buffer().iter() {
-        println!("{:016b}", sample.read());
+    for sample in data {
+        println!("{:016b}", sample);
     }
 }
@@ -0,0 +1,14 @@
+extern crate redpitaya_mercury;
+
+use std::process;
+use redpitaya_mercury::prelude::*;
+use redpitaya_mercury::selftest::SelfTest;
+
+fn main() {
+    let mut mgmt = Management::new().unwrap();
+    let report = SelfTest::new().run(&mut mgmt).unwrap();
+    println!("{}", report);
+    if !report.passed() {
+        process::exit(1);
+    }
+}
@@ -5,14 +5,14 @@ use redpitaya_mercury::prelude::*;
 use redpitaya_mercury::la;
 
 fn main() {
-    let hwid = HwId::new();
+    let hwid = HwId::new().unwrap();
     hwid.show();
 
-    let mut ps2pl = Interrupt::new();
-    ps2pl.enable();
+    let mut ps2pl = Interrupt::new().unwrap();
+    ps2pl.enable().unwrap();
 
     // setup logic analyzer
-    let mut la = LogicAnalyzer::new();
+    let mut la = LogicAnalyzer::new().unwrap();
     la.default();
     la.set_input_mask(0xffff);
     la.set_trigger_pre(la::BUFFER_SIZE / 2);
@@ -29,11 +29,11 @@ fn main() {
     la.start_trigger();
 
     println!("Wait for interrupt");
-    let res = ps2pl.wfi();
+    let res = ps2pl.wfi().unwrap();
     println!("Received interrupt {}", res);
 
     // print data
-    //for sample in la.buffer().iter() {
-    //    println!("{:016b}", sample.read());
+    //for sample in la.data(la::BUFFER_SIZE as usize) {
+    //    println!("{:016b}", sample);
     //}
 }
@@ -1,6 +1,10 @@
+use std::cmp;
 use uio::*;
 use volatile_register::{RW, RO};
 
+/// Set in the status counters once they overflowed.
+pub const STATUS_OVERFLOW_MASK: u32 = 1 << 31;
+
 #[repr(C)]
 pub struct AcquireRegs {
     /// Delay pre trigger
@@ -47,8 +51,12 @@ pub trait AcquireRegsAPI {
     /// Set post trigger delay.
     fn set_trigger_post(&mut self, delay: u32);
     /// Pre trigger sample counter status.
+    ///
+    /// The MSB is set when the counter overflowed.
     fn trigger_pre_status(&self) -> u32;
     /// Post trigger sample counter status.
+    ///
+    /// The MSB is set when the counter overflowed.
     fn trigger_post_status(&self) -> u32;
 }
 
@@ -72,3 +80,59 @@ impl AcquireRegsAPI for AcquireRegs {
         self.status_post.read()
     }
 }
+
+/// Mask out overflow bit and sum pre and post trigger counters.
+///
+/// Returns the position in a circular buffer of `size` samples the next
+/// sample will be written to.
+pub fn pointer<A: AcquireRegsAPI + ?Sized>(acq: &A, size: usize) -> usize {
+    let pre = acq.trigger_pre_status() & !STATUS_OVERFLOW_MASK;
+    let post = acq.trigger_post_status() & !STATUS_OVERFLOW_MASK;
+    pre.wrapping_add(post) as usize % size
+}
+
+/// Number of valid samples stored in a circular buffer of `size` samples.
+pub fn available<A: AcquireRegsAPI + ?Sized>(acq: &A, size: usize) -> usize {
+    let pre = acq.trigger_pre_status();
+    let post = acq.trigger_post_status();
+    if (pre | post) & STATUS_OVERFLOW_MASK != 0 {
+        return size;
+    }
+    cmp::min(pre as u64 + post as u64, size as u64) as usize
+}
+
+/// Buffer indices of the last `num_samples` samples in chronological order.
+///
+/// At most `available` samples are returned.
+pub fn indices<A: AcquireRegsAPI + ?Sized>(acq: &A, size: usize, num_samples: usize)
+                                           -> impl Iterator<Item = usize> {
+    let num_samples = cmp::min(num_samples, available(acq, size));
+    let start = (size + pointer(acq, size) - num_samples) % size;
+    (0..num_samples).map(move |i| (start + i) % size)
+}
+
+/// Position of the trigger sample among the last `num_samples` samples.
+///
+/// The trigger sample is the first sample counted by the post trigger
+/// counter, it directly follows the samples counted by the pre trigger
+/// counter. Returns `None` if no trigger occurred or the trigger sample is
+/// not part of the last `num_samples` samples.
+pub fn trigger_position<A: AcquireRegsAPI + ?Sized>(acq: &A, triggered: bool, size: usize,
+                                                    num_samples: usize) -> Option<usize> {
+    let pre = acq.trigger_pre_status();
+    let post = acq.trigger_post_status();
+    if !triggered && post == 0 {
+        return None;
+    }
+    if post & STATUS_OVERFLOW_MASK != 0 {
+        return None;
+    }
+    let num_samples = cmp::min(num_samples, available(acq, size)) as i64;
+    let trigger = (pre & !STATUS_OVERFLOW_MASK) as i64;
+    let first = trigger + post as i64 - num_samples;
+    let position = trigger - first;
+    if position < 0 || position >= num_samples {
+        return None;
+    }
+    Some(position as usize)
+}
@@ -0,0 +1,130 @@
+use std::future::Future;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use error::{Error, Result};
+use futures_core::Stream;
+use interrupts::Interrupt;
+use libc;
+use tokio::io::unix::AsyncFd;
+
+/// `ps2pl` interrupt registered with the tokio reactor.
+///
+/// Allows waiting for the FPGA modules and serving sockets on the same
+/// runtime. Must be created from within a tokio runtime.
+pub struct AsyncIrq {
+    fd: AsyncFd<Interrupt>,
+    armed: bool,
+}
+
+impl AsyncIrq {
+    pub fn new(irq: Interrupt) -> Result<Self> {
+        let uio_num = irq.uio_num();
+        let res = unsafe {
+            let flags = libc::fcntl(irq.as_raw_fd(), libc::F_GETFL);
+            libc::fcntl(irq.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK)
+        };
+        if res < 0 {
+            return Err(Error::Irq(uio_num, io::Error::last_os_error()));
+        }
+        match AsyncFd::new(irq) {
+            Ok(fd) => Ok(AsyncIrq { fd, armed: false }),
+            Err(err) => Err(Error::Irq(uio_num, err)),
+        }
+    }
+
+    /// Enable the interrupt unless it is already enabled.
+    ///
+    /// Waiting enables the interrupt as well. Enable it before checking the
+    /// status of a module, so a completion right after the check is not
+    /// missed by the following `wait_irq`.
+    pub fn arm(&mut self) -> Result<()> {
+        if !self.armed {
+            self.fd.get_mut().enable()?;
+            self.armed = true;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next interrupt.
+    ///
+    /// Resolves to the total number of interrupts.
+    pub fn wait_irq(&mut self) -> WaitIrq<'_> {
+        WaitIrq { irq: self }
+    }
+
+    fn poll_irq(&mut self, cx: &mut Context) -> Poll<Result<u32>> {
+        let uio_num = self.fd.get_ref().uio_num();
+        self.arm()?;
+        loop {
+            let mut guard = match self.fd.poll_read_ready_mut(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(Error::Irq(uio_num, err))),
+                Poll::Pending => return Poll::Pending,
+            };
+            match guard.try_io(|fd| fd.get_mut().read_count()) {
+                Ok(Ok(count)) => {
+                    self.armed = false;
+                    return Poll::Ready(Ok(count));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(Error::Irq(uio_num, err))),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Stream of interrupt counts.
+impl Stream for AsyncIrq {
+    type Item = Result<u32>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_irq(cx).map(Some)
+    }
+}
+
+/// Future returned by `AsyncIrq::wait_irq`.
+pub struct WaitIrq<'a> {
+    irq: &'a mut AsyncIrq,
+}
+
+impl<'a> Future for WaitIrq<'a> {
+    type Output = Result<u32>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.irq.poll_irq(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::os::fd::OwnedFd;
+    use tokio::runtime::Builder;
+    use super::*;
+
+    #[test]
+    fn wait_irq() {
+        let (uio, mut kernel) = UnixStream::pair().unwrap();
+        let irq = Interrupt::from_file(3, File::from(OwnedFd::from(uio)));
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let _guard = rt.enter();
+        let mut irq = AsyncIrq::new(irq).unwrap();
+        let mut control = [0; 4];
+
+        irq.arm().unwrap();
+        kernel.read_exact(&mut control).unwrap();
+        assert_eq!(control, [1, 0, 0, 0]);
+        kernel.write_all(&[5, 0, 0, 0]).unwrap();
+        assert_eq!(rt.block_on(irq.wait_irq()).unwrap(), 5);
+
+        // Waiting again enables the interrupt again.
+        kernel.write_all(&[6, 0, 0, 0]).unwrap();
+        assert_eq!(rt.block_on(irq.wait_irq()).unwrap(), 6);
+        kernel.read_exact(&mut control).unwrap();
+        assert_eq!(control, [1, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,224 @@
+use error::{Error, Result};
+use hwid;
+use sysfs::Sysfs;
+
+/// Red Pitaya board family the bitstream was built for.
+///
+/// Only the STEMlab 125-14 is identified from the `hwid` register, other
+/// families can be selected with `set_board` on the devices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BoardFamily {
+    /// Family not recognised in the `hwid` register, treated like a
+    /// STEMlab 125-14.
+    Unknown,
+    /// STEMlab 125-10.
+    Stemlab125_10,
+    /// STEMlab 125-14.
+    Stemlab125_14,
+    /// SDRlab 122-16.
+    Sdrlab122_16,
+    /// SIGNALlab 250-12.
+    Signallab250_12,
+    /// STEMlab 125-14 with four inputs and no outputs.
+    Stemlab125_14_4Input,
+}
+
+/// FPGA module of the mercury firmware.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Module {
+    Gen0 = 1 << 0,
+    Gen1 = 1 << 1,
+    Osc0 = 1 << 2,
+    Osc1 = 1 << 3,
+    Lg = 1 << 4,
+    La = 1 << 5,
+}
+
+pub const MODULES: [Module; 6] = [
+    Module::Gen0,
+    Module::Gen1,
+    Module::Osc0,
+    Module::Osc1,
+    Module::Lg,
+    Module::La,
+];
+
+impl Module {
+    /// Generator module of `channel`.
+    pub fn gen(channel: usize) -> Option<Module> {
+        [Module::Gen0, Module::Gen1].get(channel).cloned()
+    }
+
+    /// Oscilloscope module of `channel`.
+    pub fn osc(channel: usize) -> Option<Module> {
+        [Module::Osc0, Module::Osc1].get(channel).cloned()
+    }
+
+    /// Name of the UIO device of the module, see `MERCURY_DEVICES`.
+    pub fn uio_name(&self) -> &'static str {
+        match *self {
+            Module::Gen0 => "gen0",
+            Module::Gen1 => "gen1",
+            Module::Osc0 => "osc0",
+            Module::Osc1 => "osc1",
+            Module::Lg => "lg",
+            Module::La => "la",
+        }
+    }
+}
+
+/// Board type field of the `hwid` register.
+const HWID_BOARD_MASK: u32 = 0xf;
+/// Board type of the STEMlab 125-14.
+const HWID_BOARD_STEMLAB_125_14: u32 = 1;
+/// Module field of the `hwid` register, one bit per `Module`.
+const HWID_MODULES_SHIFT: u32 = 8;
+const HWID_MODULES_MASK: u32 = 0xff << HWID_MODULES_SHIFT;
+
+/// Board and FPGA variant decoded from the `hwid` register.
+///
+/// `hwid[3:0]` holds the board type as in the housekeeping module of the
+/// Red Pitaya FPGA, which reports `1` for the STEMlab 125-14. No other
+/// board types are documented, so every other value, like the `0` of
+/// bitstreams that leave `hwid` unset, decodes to `BoardFamily::Unknown`.
+///
+/// Bitstreams may list the modules they contain in `hwid[15:8]`, one bit
+/// per `Module`. If the field is empty all modules are assumed present.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BoardInfo {
+    family: BoardFamily,
+    modules: u32,
+}
+
+impl BoardInfo {
+    /// Decode a `hwid` register value.
+    pub fn from_hwid(hwid: u32) -> Self {
+        let family = match hwid & HWID_BOARD_MASK {
+            HWID_BOARD_STEMLAB_125_14 => BoardFamily::Stemlab125_14,
+            _ => BoardFamily::Unknown,
+        };
+        match (hwid & HWID_MODULES_MASK) >> HWID_MODULES_SHIFT {
+            0 => BoardInfo::with_family(family),
+            modules => BoardInfo { family, modules },
+        }
+    }
+
+    /// Decode the `hwid` register of the bitstream loaded in the device tree
+    /// of `sysfs`, see `hwid::identity`.
+    pub fn detect(sysfs: &Sysfs) -> Result<Self> {
+        Ok(BoardInfo::from_hwid(hwid::identity(sysfs)?.hwid))
+    }
+
+    /// Board with all modules, as assumed for devices created from mappings.
+    pub fn with_family(family: BoardFamily) -> Self {
+        let modules = MODULES.iter().fold(0, |mask, module| mask | *module as u32);
+        BoardInfo { family, modules }
+    }
+
+    pub fn family(&self) -> BoardFamily {
+        self.family
+    }
+
+    /// ADC resolution in bits.
+    pub fn adc_bits(&self) -> u32 {
+        match self.family {
+            BoardFamily::Stemlab125_10 => 10,
+            BoardFamily::Stemlab125_14 | BoardFamily::Stemlab125_14_4Input |
+            BoardFamily::Unknown => 14,
+            BoardFamily::Sdrlab122_16 => 16,
+            BoardFamily::Signallab250_12 => 12,
+        }
+    }
+
+    /// DAC resolution in bits.
+    pub fn dac_bits(&self) -> u32 {
+        match self.family {
+            BoardFamily::Stemlab125_10 => 10,
+            _ => 14,
+        }
+    }
+
+    /// Number of analog inputs.
+    pub fn adc_channels(&self) -> usize {
+        match self.family {
+            BoardFamily::Stemlab125_14_4Input => 4,
+            _ => 2,
+        }
+    }
+
+    /// Number of analog outputs.
+    pub fn dac_channels(&self) -> usize {
+        match self.family {
+            BoardFamily::Stemlab125_14_4Input => 0,
+            _ => 2,
+        }
+    }
+
+    /// ADC and DAC sampling frequency in Hz.
+    pub fn sampling_frequency(&self) -> u32 {
+        match self.family {
+            BoardFamily::Sdrlab122_16 => 122_880_000,
+            BoardFamily::Signallab250_12 => 250_000_000,
+            _ => 125_000_000,
+        }
+    }
+
+    /// Whether `module` is present in the bitstream.
+    pub fn has_module(&self, module: Module) -> bool {
+        self.modules & module as u32 != 0
+    }
+
+    /// Modules present in the bitstream.
+    pub fn modules(&self) -> impl Iterator<Item = Module> {
+        let modules = self.modules;
+        MODULES.iter().cloned().filter(move |module| modules & *module as u32 != 0)
+    }
+
+    /// Fail with `Error::MissingModule` if `module` is not in the bitstream.
+    pub fn require(&self, module: Module) -> Result<()> {
+        if self.has_module(module) {
+            Ok(())
+        } else {
+            Err(Error::MissingModule(module))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        // Board type 1 of the Red Pitaya housekeeping module.
+        let board = BoardInfo::from_hwid(0x0000_0001);
+        assert_eq!(board.family(), BoardFamily::Stemlab125_14);
+        assert_eq!(board.adc_bits(), 14);
+        assert_eq!(board.dac_bits(), 14);
+        assert_eq!(board.modules().count(), MODULES.len());
+    }
+
+    #[test]
+    fn modules() {
+        let board = BoardInfo::from_hwid(0x0f01);
+        assert_eq!(board.family(), BoardFamily::Stemlab125_14);
+        assert!(board.has_module(Module::Osc1) && !board.has_module(Module::Lg));
+        assert_eq!(board.modules().count(), 4);
+        match board.require(Module::La) {
+            Err(Error::MissingModule(Module::La)) => {}
+            _ => panic!("missing module should be reported"),
+        }
+    }
+
+    #[test]
+    fn unrecognised() {
+        for &hwid in &[0, 2, 3, 4, 5, 0x0f00, 0xdead_beef] {
+            let board = BoardInfo::from_hwid(hwid);
+            assert_eq!(board.family(), BoardFamily::Unknown);
+            assert_eq!(board.adc_bits(), 14);
+            assert_eq!(board.dac_bits(), 14);
+            assert_eq!(board.sampling_frequency(), 125_000_000);
+        }
+        assert_eq!(BoardInfo::from_hwid(0).modules().count(), MODULES.len());
+    }
+}
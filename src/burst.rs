@@ -0,0 +1,134 @@
+use error::{Error, Result};
+use uio::*;
+use volatile_register::{RO, RW};
+
+const BMD_ENABLE_MASK: u32 = 1 << 0;
+const BMD_INFINITE_MASK: u32 = 1 << 1;
+
+#[repr(C)]
+pub struct BurstRegs {
+    /// Burst mode ([1] - infinite, [0] - burst enable)
+    cfg_bmd: RW<u32>,
+    /// Burst data length
+    cfg_bdl: RW<u32>,
+    /// Burst period length
+    cfg_bln: RW<u32>,
+    /// Burst repetitions
+    cfg_bnm: RW<u32>,
+    /// Burst length status
+    sts_bln: RO<u32>,
+    /// Burst repetitions status
+    sts_bnm: RO<u32>,
+}
+
+impl Default for BurstRegs {
+    fn default(&mut self) {
+        unsafe {
+            self.cfg_bmd.write(0);
+            self.cfg_bdl.write(0);
+            self.cfg_bln.write(0);
+            self.cfg_bnm.write(0);
+        }
+    }
+}
+
+impl Show for BurstRegs {
+    fn show(&self) {
+        println!("cfg_bmd = {:x}", self.cfg_bmd.read());
+        println!("cfg_bdl = {:x}", self.cfg_bdl.read());
+        println!("cfg_bln = {:x}", self.cfg_bln.read());
+        println!("cfg_bnm = {:x}", self.cfg_bnm.read());
+        println!("sts_bln = {:x}", self.sts_bln.read());
+        println!("sts_bnm = {:x}", self.sts_bnm.read());
+    }
+}
+
+pub trait BurstRegsAPI {
+    /// Get burst mode.
+    fn burst(&self) -> bool;
+    /// Set burst mode.
+    ///
+    /// In burst mode the output is active for `burst_data_length` samples
+    /// of each `burst_period_length` samples. Otherwise the buffer is
+    /// output periodically.
+    fn set_burst(&mut self, burst: bool);
+    /// Get infinite burst repetitions.
+    fn burst_infinite(&self) -> bool;
+    /// Set infinite burst repetitions.
+    fn set_burst_infinite(&mut self, infinite: bool);
+    /// Get number of samples output in a burst.
+    fn burst_data_length(&self) -> u32;
+    /// Set number of samples output in a burst.
+    ///
+    /// Fails with `Error::InvalidValue` if `len` is 0.
+    fn set_burst_data_length(&mut self, len: u32) -> Result<()>;
+    /// Get burst period length in samples.
+    fn burst_period_length(&self) -> u32;
+    /// Set burst period length in samples.
+    ///
+    /// Fails with `Error::InvalidValue` if `len` is 0.
+    fn set_burst_period_length(&mut self, len: u32) -> Result<()>;
+    /// Get number of burst repetitions.
+    fn burst_repetitions(&self) -> u32;
+    /// Set number of burst repetitions.
+    ///
+    /// Fails with `Error::InvalidValue` if `num` is 0.
+    fn set_burst_repetitions(&mut self, num: u32) -> Result<()>;
+    /// Burst length counter status.
+    fn burst_length_status(&self) -> u32;
+    /// Burst repetitions counter status.
+    fn burst_repetitions_status(&self) -> u32;
+}
+
+impl BurstRegsAPI for BurstRegs {
+    fn burst(&self) -> bool {
+        self.cfg_bmd.read() & BMD_ENABLE_MASK == BMD_ENABLE_MASK
+    }
+    fn set_burst(&mut self, burst: bool) {
+        let value = self.cfg_bmd.read() & !BMD_ENABLE_MASK;
+        unsafe { self.cfg_bmd.write(value | burst as u32); }
+    }
+    fn burst_infinite(&self) -> bool {
+        self.cfg_bmd.read() & BMD_INFINITE_MASK == BMD_INFINITE_MASK
+    }
+    fn set_burst_infinite(&mut self, infinite: bool) {
+        let value = self.cfg_bmd.read() & !BMD_INFINITE_MASK;
+        unsafe { self.cfg_bmd.write(value | (infinite as u32) << 1); }
+    }
+    fn burst_data_length(&self) -> u32 {
+        self.cfg_bdl.read().wrapping_add(1)
+    }
+    fn set_burst_data_length(&mut self, len: u32) -> Result<()> {
+        if len == 0 {
+            return Err(Error::InvalidValue("burst data length", len));
+        }
+        unsafe { self.cfg_bdl.write(len - 1); }
+        Ok(())
+    }
+    fn burst_period_length(&self) -> u32 {
+        self.cfg_bln.read().wrapping_add(1)
+    }
+    fn set_burst_period_length(&mut self, len: u32) -> Result<()> {
+        if len == 0 {
+            return Err(Error::InvalidValue("burst period length", len));
+        }
+        unsafe { self.cfg_bln.write(len - 1); }
+        Ok(())
+    }
+    fn burst_repetitions(&self) -> u32 {
+        self.cfg_bnm.read().wrapping_add(1)
+    }
+    fn set_burst_repetitions(&mut self, num: u32) -> Result<()> {
+        if num == 0 {
+            return Err(Error::InvalidValue("burst repetitions", num));
+        }
+        unsafe { self.cfg_bnm.write(num - 1); }
+        Ok(())
+    }
+    fn burst_length_status(&self) -> u32 {
+        self.sts_bln.read()
+    }
+    fn burst_repetitions_status(&self) -> u32 {
+        self.sts_bnm.read()
+    }
+}
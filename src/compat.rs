@@ -0,0 +1,172 @@
+use error::{Error, Result};
+use hwid;
+use sysfs::Sysfs;
+
+/// Version of the register layouts (`LaRegs`, `MgmtRegs`, ...) of this crate.
+pub const REGISTER_MAP_VERSION: u32 = 1;
+
+/// Git hash of the mercury bitstream shipped with the crate, set with the
+/// `MERCURY_FIRMWARE_GITH` environment variable when building it.
+const SHIPPED_FIRMWARE: Option<&str> = option_env!("MERCURY_FIRMWARE_GITH");
+
+/// How device constructors react to the loaded bitstream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompatPolicy {
+    /// Don't check the bitstream.
+    Ignore,
+    /// Accept unknown bitstreams, fail on incompatible ones.
+    Permissive,
+    /// Fail on unknown and incompatible bitstreams.
+    Strict,
+}
+
+/// Outcome of a successful compatibility check.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compat {
+    /// The bitstream was not checked, see `CompatPolicy::Ignore`.
+    Unchecked,
+    /// The bitstream is not in the compatibility table.
+    Unknown,
+    /// The bitstream uses the register map of this crate.
+    Compatible,
+}
+
+/// Git hashes of mercury bitstreams and their register map version, and
+/// the policy device constructors apply to the loaded bitstream.
+///
+/// Every `Sysfs` carries a table, see `Sysfs::set_compat`. It starts with
+/// the bitstream the crate was built for, if `MERCURY_FIRMWARE_GITH` was
+/// set, and the `Permissive` policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatTable {
+    policy: CompatPolicy,
+    firmware: Vec<(String, u32)>,
+}
+
+impl CompatTable {
+    pub fn new() -> Self {
+        let firmware = SHIPPED_FIRMWARE.iter()
+            .map(|gith| (gith.to_lowercase(), REGISTER_MAP_VERSION))
+            .collect();
+        CompatTable { policy: CompatPolicy::Permissive, firmware }
+    }
+
+    /// Get the policy applied by device constructors.
+    pub fn policy(&self) -> CompatPolicy {
+        self.policy
+    }
+
+    /// Set the policy applied by device constructors.
+    pub fn set_policy(&mut self, policy: CompatPolicy) {
+        self.policy = policy;
+    }
+
+    /// Add a bitstream to the table, replacing an earlier entry.
+    ///
+    /// `gith` is the hash as returned by `HwId::gith`.
+    pub fn register_firmware(&mut self, gith: &str, register_map: u32) {
+        self.firmware.retain(|(hash, _)| hash != gith);
+        self.firmware.push((gith.to_string(), register_map));
+    }
+
+    /// Register map version of the bitstream with git hash `gith`.
+    pub fn firmware_version(&self, gith: &str) -> Option<u32> {
+        self.firmware.iter()
+            .find(|(hash, _)| hash == gith)
+            .map(|&(_, version)| version)
+    }
+
+    /// Check a bitstream against the table.
+    pub fn check_gith(&self, gith: &str) -> Result<Compat> {
+        if self.policy == CompatPolicy::Ignore {
+            return Ok(Compat::Unchecked);
+        }
+        match self.firmware_version(gith) {
+            Some(REGISTER_MAP_VERSION) => Ok(Compat::Compatible),
+            Some(version) => Err(Error::IncompatibleFirmware(gith.to_string(), Some(version))),
+            None if self.policy == CompatPolicy::Strict =>
+                Err(Error::IncompatibleFirmware(gith.to_string(), None)),
+            None => Ok(Compat::Unknown),
+        }
+    }
+}
+
+impl Default for CompatTable {
+    fn default() -> Self {
+        CompatTable::new()
+    }
+}
+
+/// Check the bitstream loaded in the device tree of `sysfs` against its
+/// table, see `Sysfs::compat`.
+///
+/// The git hash is read once per sysfs root, see `hwid::identity`.
+pub fn check(sysfs: &Sysfs) -> Result<Compat> {
+    let table = sysfs.compat();
+    if table.policy() == CompatPolicy::Ignore {
+        return Ok(Compat::Unchecked);
+    }
+    table.check_gith(&hwid::identity(sysfs)?.gith)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+    use fake::{self, FakeSysfs};
+    use lock::LockMode;
+    use management::Management;
+    use super::*;
+
+    #[test]
+    fn table() {
+        let gith = "0123456789abcdef0123456789abcdef01234567";
+        let mut table = CompatTable::new();
+        assert_eq!(table.check_gith(gith).unwrap(), Compat::Unknown);
+        table.set_policy(CompatPolicy::Ignore);
+        assert_eq!(table.check_gith(gith).unwrap(), Compat::Unchecked);
+        table.set_policy(CompatPolicy::Strict);
+        assert!(table.check_gith(gith).is_err());
+        table.register_firmware(gith, REGISTER_MAP_VERSION);
+        assert_eq!(table.check_gith(gith).unwrap(), Compat::Compatible);
+        table.register_firmware(gith, REGISTER_MAP_VERSION + 1);
+        assert_eq!(table.firmware_version(gith), Some(REGISTER_MAP_VERSION + 1));
+        table.set_policy(CompatPolicy::Permissive);
+        match table.check_gith(gith) {
+            Err(Error::IncompatibleFirmware(ref hash, Some(_))) if hash == gith => {}
+            _ => panic!("incompatible firmware should be rejected"),
+        }
+        // Tables are independent of each other.
+        assert_eq!(CompatTable::new().firmware_version(gith), None);
+    }
+
+    #[test]
+    fn constructors() {
+        let fake = FakeSysfs::new();
+        fake.add_mercury_devices(fake::HWID);
+        let mut sysfs = fake.sysfs();
+        // gith[0] of the hwid device, any hash other than the shipped one.
+        let mut file = OpenOptions::new().write(true).open(sysfs.dev_path(0)).unwrap();
+        file.seek(SeekFrom::Start(0x20)).unwrap();
+        file.write_all(&[0x5a; 4]).unwrap();
+        let gith = hwid::identity(&sysfs).unwrap().gith;
+        assert_eq!(gith, "000000000000000000000000000000005a5a5a5a");
+        let mgmt = Management::with_sysfs(&sysfs, LockMode::Try).unwrap();
+        assert_eq!(mgmt.compat(), Compat::Unknown);
+        drop(mgmt);
+
+        let mut table = CompatTable::new();
+        table.set_policy(CompatPolicy::Strict);
+        sysfs.set_compat(table.clone());
+        match Management::with_sysfs(&sysfs, LockMode::Try) {
+            Err(Error::IncompatibleFirmware(ref hash, None)) if *hash == gith => {}
+            _ => panic!("unknown firmware should be rejected"),
+        }
+        table.register_firmware(&gith, REGISTER_MAP_VERSION);
+        sysfs.set_compat(table);
+        let mgmt = Management::with_sysfs(&sysfs, LockMode::Try).unwrap();
+        assert_eq!(mgmt.compat(), Compat::Compatible);
+        // Other sysfs handles keep their own table.
+        assert_eq!(check(&fake.sysfs()).unwrap(), Compat::Unknown);
+    }
+}
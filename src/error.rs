@@ -0,0 +1,121 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+use std::time::Duration;
+use board::Module;
+use compat::REGISTER_MAP_VERSION;
+
+#[derive(Debug)]
+pub enum Error {
+    /// UIO device symlink could not be read.
+    DeviceNotFound(PathBuf, io::Error),
+    /// UIO device symlink does not point to a `uioN` device.
+    InvalidSymlink(PathBuf),
+    /// No UIO device with the given name was found.
+    UioNotFound(String),
+    /// UIO device attribute in sysfs could not be read.
+    Sysfs(PathBuf, io::Error),
+    /// Reading or writing a firmware file failed.
+    Firmware(PathBuf, io::Error),
+    /// UIO device could not be opened.
+    Open(usize, io::Error),
+    /// Mapping of a UIO device could not be mapped.
+    Mmap(usize, usize),
+    /// Mapping of the given size is smaller than the required size.
+    MappingTooSmall(usize, usize),
+    /// UIO device is locked by another process with the given pid.
+    Busy(usize, Option<u32>),
+    /// Locking a UIO device failed.
+    Lock(usize, io::Error),
+    /// Enabling, disabling or waiting for an interrupt failed.
+    Irq(usize, io::Error),
+    /// Register contains a value that is not valid for the field.
+    InvalidRegister(&'static str, u32),
+    /// Value is out of range for the setting.
+    InvalidValue(&'static str, u32),
+    /// Logic analyzer channel is out of range.
+    InvalidChannel(usize),
+    /// Data of the given length does not fit into a buffer of the given size.
+    BufferOverflow(usize, usize),
+    /// Data to upload into a buffer is empty.
+    EmptyBuffer,
+    /// Bitstream with the given git hash is unknown or has the given
+    /// register map version.
+    IncompatibleFirmware(String, Option<u32>),
+    /// Module is not present in the loaded bitstream.
+    MissingModule(Module),
+    /// Device was created without an interrupt.
+    NoIrq,
+    /// Operation did not complete within the given time.
+    Timeout(Duration),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::DeviceNotFound(ref path, ref err) =>
+                write!(f, "device {} not found: {}", path.display(), err),
+            Error::InvalidSymlink(ref path) =>
+                write!(f, "{} does not point to a uio device", path.display()),
+            Error::UioNotFound(ref name) =>
+                write!(f, "no UIO device named {}", name),
+            Error::Sysfs(ref path, ref err) =>
+                write!(f, "failed to read {}: {}", path.display(), err),
+            Error::Firmware(ref path, ref err) =>
+                write!(f, "failed to load firmware through {}: {}", path.display(), err),
+            Error::Open(uio, ref err) =>
+                write!(f, "failed to open uio{}: {}", uio, err),
+            Error::Mmap(uio, mapping) =>
+                write!(f, "failed to map mapping {} of uio{}", mapping, uio),
+            Error::MappingTooSmall(len, size) =>
+                write!(f, "mapping of {} bytes is smaller than {} bytes", len, size),
+            Error::Busy(uio, Some(pid)) =>
+                write!(f, "device uio{} busy (held by pid {})", uio, pid),
+            Error::Busy(uio, None) =>
+                write!(f, "device uio{} busy", uio),
+            Error::Lock(uio, ref err) =>
+                write!(f, "failed to lock uio{}: {}", uio, err),
+            Error::Irq(uio, ref err) =>
+                write!(f, "interrupt error on uio{}: {}", uio, err),
+            Error::InvalidRegister(name, value) =>
+                write!(f, "invalid value {:#x} in register {}", value, name),
+            Error::InvalidValue(name, value) =>
+                write!(f, "invalid value {} for {}", value, name),
+            Error::InvalidChannel(channel) =>
+                write!(f, "logic analyzer has no channel {}", channel),
+            Error::BufferOverflow(len, size) =>
+                write!(f, "{} samples do not fit into buffer of {} samples", len, size),
+            Error::EmptyBuffer =>
+                write!(f, "no samples to upload"),
+            Error::IncompatibleFirmware(ref gith, Some(version)) =>
+                write!(f, "firmware {} has register map version {}, expected {}",
+                       gith, version, REGISTER_MAP_VERSION),
+            Error::IncompatibleFirmware(ref gith, None) =>
+                write!(f, "firmware {} is unknown", gith),
+            Error::MissingModule(module) =>
+                write!(f, "{:?} is not present in the loaded bitstream", module),
+            Error::NoIrq =>
+                write!(f, "device has no interrupt"),
+            Error::Timeout(timeout) =>
+                write!(f, "timed out after {:?}", timeout),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::DeviceNotFound(_, ref err) |
+            Error::Sysfs(_, ref err) |
+            Error::Firmware(_, ref err) |
+            Error::Open(_, ref err) |
+            Error::Lock(_, ref err) |
+            Error::Irq(_, ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use std::convert::TryFrom;
+use std::ops::BitOr;
+use error::{self, Error};
 use uio;
 use volatile_register::RW;
 
@@ -36,6 +38,7 @@ impl TryFrom<u32> for SyncSource {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TriggerSource {
     Gen0 = 1 << 0,
     Gen1 = 1 << 1,
@@ -65,6 +68,102 @@ impl TryFrom<u32> for TriggerSource {
     }
 }
 
+const TRIGGER_SOURCES: [TriggerSource; 6] = [
+    TriggerSource::Gen0,
+    TriggerSource::Gen1,
+    TriggerSource::Osc0,
+    TriggerSource::Osc1,
+    TriggerSource::Lg,
+    TriggerSource::La,
+];
+
+/// Set of hardware trigger sources.
+///
+/// The trigger fires when any of the sources in the set triggers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct TriggerSources(u32);
+
+impl TriggerSources {
+    /// Set containing no trigger source.
+    pub fn empty() -> Self {
+        TriggerSources(0)
+    }
+
+    /// Set containing all trigger sources.
+    pub fn all() -> Self {
+        TRIGGER_SOURCES.iter().fold(TriggerSources::empty(), |set, src| set | *src)
+    }
+
+    /// Create a set from a `cfg_trigger` bit mask.
+    ///
+    /// Returns `None` if a bit not corresponding to a trigger source is set.
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        if bits & !TriggerSources::all().bits() == 0 {
+            Some(TriggerSources(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Create a set from a `cfg_trigger` bit mask ignoring unknown bits.
+    pub fn from_bits_truncate(bits: u32) -> Self {
+        TriggerSources(bits & TriggerSources::all().bits())
+    }
+
+    /// Bit mask as written to `cfg_trigger`.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, source: TriggerSource) -> bool {
+        self.0 & source as u32 != 0
+    }
+
+    pub fn insert(&mut self, source: TriggerSource) {
+        self.0 |= source as u32;
+    }
+
+    pub fn remove(&mut self, source: TriggerSource) {
+        self.0 &= !(source as u32);
+    }
+
+    pub fn union(&self, other: TriggerSources) -> Self {
+        TriggerSources(self.0 | other.0)
+    }
+
+    /// Iterate over the trigger sources in the set.
+    pub fn iter(&self) -> impl Iterator<Item = TriggerSource> {
+        let set = *self;
+        TRIGGER_SOURCES.iter().cloned().filter(move |src| set.contains(*src))
+    }
+}
+
+impl From<TriggerSource> for TriggerSources {
+    fn from(source: TriggerSource) -> Self {
+        TriggerSources(source as u32)
+    }
+}
+
+impl<T: Into<TriggerSources>> BitOr<T> for TriggerSources {
+    type Output = TriggerSources;
+
+    fn bitor(self, other: T) -> TriggerSources {
+        self.union(other.into())
+    }
+}
+
+impl<T: Into<TriggerSources>> BitOr<T> for TriggerSource {
+    type Output = TriggerSources;
+
+    fn bitor(self, other: T) -> TriggerSources {
+        TriggerSources::from(self).union(other.into())
+    }
+}
+
 #[repr(C)]
 pub struct EventRegs {
     ctl_status: RW<u32>,
@@ -106,13 +205,16 @@ pub trait EventRegsAPI {
     /// Trigger status
     fn is_triggered(&self) -> bool;
     /// Get software event source
-    fn sync_source(&self) -> SyncSource;
+    fn sync_source(&self) -> error::Result<SyncSource>;
     /// Set software event source
     fn set_sync_source(&mut self, event: SyncSource);
-    /// Get hardware trigger source
-    fn trigger_source(&self) -> TriggerSource;
-    /// Set hardware trigger source
-    fn set_trigger_source(&mut self, trigger: TriggerSource);
+    /// Get hardware trigger sources
+    fn trigger_source(&self) -> TriggerSources;
+    /// Set hardware trigger sources
+    ///
+    /// Accepts a single `TriggerSource` or a combination like
+    /// `TriggerSource::La | TriggerSource::Osc0`.
+    fn set_trigger_source<T: Into<TriggerSources>>(&mut self, trigger: T);
 }
 
 impl EventRegsAPI for EventRegs {
@@ -144,19 +246,85 @@ impl EventRegsAPI for EventRegs {
         self.ctl_status.read() & CTL_TRIGGER_MASK == CTL_TRIGGER_MASK
     }
 
-    fn sync_source(&self) -> SyncSource {
-        SyncSource::try_from(self.cfg_event.read()).unwrap()
+    fn sync_source(&self) -> error::Result<SyncSource> {
+        let value = self.cfg_event.read();
+        SyncSource::try_from(value).map_err(|_| Error::InvalidRegister("cfg_event", value))
     }
 
     fn set_sync_source(&mut self, event: SyncSource) {
         unsafe { self.cfg_event.write(event as u32); }
     }
 
-    fn trigger_source(&self) -> TriggerSource {
-        TriggerSource::try_from(self.cfg_trigger.read()).unwrap()
+    fn trigger_source(&self) -> TriggerSources {
+        TriggerSources::from_bits_truncate(self.cfg_trigger.read())
     }
 
-    fn set_trigger_source(&mut self, trigger: TriggerSource) {
-        unsafe { self.cfg_trigger.write(trigger as u32); }
+    fn set_trigger_source<T: Into<TriggerSources>>(&mut self, trigger: T) {
+        unsafe { self.cfg_trigger.write(trigger.into().bits()); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{self, FakeMemory};
+    use uio::Mapping;
+    use super::*;
+
+    #[test]
+    fn from_bits() {
+        assert_eq!(TriggerSources::from_bits(0b100100), Some(TriggerSource::Osc0 | TriggerSource::La));
+        assert_eq!(TriggerSources::from_bits(0), Some(TriggerSources::empty()));
+        assert_eq!(TriggerSources::from_bits(0b1000000), None);
+        assert_eq!(TriggerSources::from_bits(0x8000_0001), None);
+        assert_eq!(TriggerSources::from_bits_truncate(0xffff_ff81).bits(), 0b000001);
+        assert_eq!(TriggerSources::from_bits_truncate(0b1000000), TriggerSources::empty());
+    }
+
+    #[test]
+    fn all() {
+        let all = TriggerSources::all();
+        assert_eq!(all.bits(), 0b111111);
+        assert!(TRIGGER_SOURCES.iter().all(|src| all.contains(*src)));
+        assert_eq!(all.iter().collect::<Vec<_>>(), TRIGGER_SOURCES.to_vec());
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut set = TriggerSources::empty();
+        assert!(set.is_empty());
+        set.insert(TriggerSource::Lg);
+        set.insert(TriggerSource::Gen1);
+        assert_eq!(set.bits(), 0b010010);
+        set.remove(TriggerSource::Lg);
+        assert!(!set.contains(TriggerSource::Lg));
+        assert!(set.contains(TriggerSource::Gen1));
+        // Removing a source not in the set leaves it unchanged.
+        set.remove(TriggerSource::La);
+        assert_eq!(set.bits(), 0b000010);
+        set.remove(TriggerSource::Gen1);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn iter_order() {
+        // Sources come out in bit order regardless of how the set was built.
+        let set = TriggerSource::La | TriggerSource::Gen0 | TriggerSource::Osc1;
+        assert_eq!(set.iter().collect::<Vec<_>>(),
+                   vec![TriggerSource::Gen0, TriggerSource::Osc1, TriggerSource::La]);
+        assert_eq!(TriggerSources::empty().iter().count(), 0);
+    }
+
+    #[test]
+    fn registers() {
+        let mem = FakeMemory::new(12);
+        let regs = unsafe { &mut *(mem.data() as *mut EventRegs) };
+        assert!(regs.trigger_source().is_empty());
+        assert_eq!(regs.trigger_source().iter().count(), 0);
+
+        regs.set_trigger_source(TriggerSource::Lg | TriggerSource::Gen1);
+        assert_eq!(fake::read(&mem, 8), 0b010010);
+        // Bits beyond the known sources are dropped when read back.
+        fake::write(&mem, 8, 0xffff_ff20);
+        assert_eq!(regs.trigger_source(), TriggerSource::La.into());
     }
 }
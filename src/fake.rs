@@ -0,0 +1,167 @@
+use std::cell::UnsafeCell;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use board::{BoardInfo, MODULES};
+use sysfs::{Sysfs, MERCURY_DEVICES};
+use uio::Mapping;
+
+/// Heap allocated memory standing in for a UIO memory map.
+///
+/// Like a real memory map the memory is owned by the device it is handed
+/// to. Preload registers before creating the device and inspect what was
+/// written through `reg_mmap` or `buffer_mmap` afterwards, using `read` and
+/// `write` below. The memory is not `Sync`, so it can't be accessed from
+/// several threads at once.
+pub struct FakeMemory {
+    words: Box<[UnsafeCell<u32>]>,
+}
+
+impl FakeMemory {
+    /// Allocate `size` bytes of zeroed memory.
+    pub fn new(size: usize) -> Self {
+        let words: Vec<UnsafeCell<u32>> = (0..size.div_ceil(4)).map(|_| UnsafeCell::new(0)).collect();
+        FakeMemory { words: words.into_boxed_slice() }
+    }
+}
+
+impl Mapping for FakeMemory {
+    fn data(&self) -> *mut u8 {
+        self.words.as_ptr() as *mut u8
+    }
+    fn len(&self) -> usize {
+        self.words.len() * 4
+    }
+}
+
+fn check(mapping: &dyn Mapping, offset: usize, size: usize) {
+    assert!(offset.is_multiple_of(size), "unaligned access at {:#x}", offset);
+    assert!(offset + size <= mapping.len(), "access at {:#x} out of bounds", offset);
+}
+
+/// Read the 32bit word at byte `offset` of `mapping`.
+pub fn read(mapping: &dyn Mapping, offset: usize) -> u32 {
+    check(mapping, offset, 4);
+    unsafe { ptr::read_volatile(mapping.data().add(offset) as *const u32) }
+}
+
+/// Write the 32bit word at byte `offset` of `mapping`.
+pub fn write(mapping: &dyn Mapping, offset: usize, value: u32) {
+    check(mapping, offset, 4);
+    unsafe { ptr::write_volatile(mapping.data().add(offset) as *mut u32, value) }
+}
+
+/// Read the 16bit word at byte `offset` of `mapping`.
+pub fn read_u16(mapping: &dyn Mapping, offset: usize) -> u16 {
+    check(mapping, offset, 2);
+    unsafe { ptr::read_volatile(mapping.data().add(offset) as *const u16) }
+}
+
+/// Write the 16bit word at byte `offset` of `mapping`.
+pub fn write_u16(mapping: &dyn Mapping, offset: usize, value: u16) {
+    check(mapping, offset, 2);
+    unsafe { ptr::write_volatile(mapping.data().add(offset) as *mut u16, value) }
+}
+
+/// Hardware id of a STEMlab 125-14 bitstream, see `add_mercury_devices`.
+pub const HWID: u32 = 0x1;
+
+/// Temporary directory laid out like `/sys/class/uio` and `/dev`.
+///
+/// Device nodes are regular files, so devices opened through `sysfs()`
+/// map plain memory. The directory is removed when dropped.
+pub struct FakeSysfs {
+    dir: PathBuf,
+}
+
+impl FakeSysfs {
+    /// Create an empty tree in the temporary directory.
+    pub fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::SeqCst);
+        let dir = ::std::env::temp_dir()
+            .join(format!("mercury-sysfs-{}-{}", process::id(), count));
+        fs::create_dir_all(dir.join("class")).unwrap();
+        fs::create_dir_all(dir.join("dev")).unwrap();
+        FakeSysfs { dir }
+    }
+
+    /// Root of the temporary directory.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Add device `uioN` called `name` with mappings of `sizes` bytes.
+    ///
+    /// The device node is sized to hold every mapping at its page offset.
+    pub fn add_device(&self, num: usize, name: &str, sizes: &[usize]) {
+        let path = self.dir.join("class").join(format!("uio{}", num));
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("name"), format!("{}\n", name)).unwrap();
+        let page_size = 4096;
+        for (map, &size) in sizes.iter().enumerate() {
+            let map_path = path.join("maps").join(format!("map{}", map));
+            fs::create_dir_all(&map_path).unwrap();
+            fs::write(map_path.join("addr"), format!("0x{:08x}\n", 0x4000_0000 + map * 0x10_0000)).unwrap();
+            fs::write(map_path.join("size"), format!("0x{:08x}\n", size)).unwrap();
+        }
+        let len = sizes.iter().enumerate()
+            .map(|(map, &size)| map * page_size + size.div_ceil(page_size) * page_size)
+            .max()
+            .unwrap_or(0);
+        // Keep contents written before the device was added, see `add_mercury_devices`.
+        let file = OpenOptions::new().write(true).create(true).truncate(false)
+            .open(self.dir.join("dev").join(format!("uio{}", num)))
+            .unwrap();
+        file.set_len(len as u64).unwrap();
+    }
+
+    /// Add the devices of the mercury firmware numbered in `MERCURY_DEVICES` order.
+    ///
+    /// The hardware id register is set to `hwid` before the device appears
+    /// and only the devices of the modules `hwid` reports are added.
+    /// Devices with a buffer get a second mapping large enough for it.
+    pub fn add_mercury_devices(&self, hwid: u32) {
+        let board = BoardInfo::from_hwid(hwid);
+        for (num, name) in MERCURY_DEVICES.iter().enumerate() {
+            if *name == "hwid" {
+                OpenOptions::new().write(true).create(true).truncate(false)
+                    .open(self.dir.join("dev").join(format!("uio{}", num)))
+                    .and_then(|mut file| file.write_all(&hwid.to_le_bytes()))
+                    .unwrap();
+            }
+            match MODULES.iter().find(|module| module.uio_name() == *name) {
+                Some(module) if board.has_module(*module) => self.add_device(num, name, &[0x1000, 0x8000]),
+                Some(_) => {}
+                None => self.add_device(num, name, &[0x1000]),
+            }
+        }
+    }
+
+    /// Remove device `uioN` from the class directory.
+    pub fn remove_device(&self, num: usize) {
+        fs::remove_dir_all(self.dir.join("class").join(format!("uio{}", num))).unwrap();
+    }
+
+    /// `Sysfs` scanning this tree.
+    pub fn sysfs(&self) -> Sysfs {
+        let mut sysfs = Sysfs::with_root(self.dir.join("class"));
+        sysfs.set_dev_dir(self.dir.join("dev"));
+        sysfs
+    }
+}
+
+impl Default for FakeSysfs {
+    fn default() -> Self {
+        FakeSysfs::new()
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,269 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use board::BoardInfo;
+use compat;
+use error::{Error, Result};
+use hwid;
+use sysfs::Sysfs;
+
+/// Directory containing the mercury bitstream and device tree overlay.
+pub const MERCURY_DIR: &str = "/opt/redpitaya/fpga/mercury";
+/// Device node the bitstream is written to.
+pub const XDEVCFG: &str = "/dev/xdevcfg";
+/// Configfs directory of device tree overlays.
+pub const OVERLAYS: &str = "/sys/kernel/config/device-tree/overlays";
+/// Name of the mercury overlay in configfs.
+pub const OVERLAY_NAME: &str = "mercury";
+
+/// Loader for the mercury FPGA bitstream and device tree overlay.
+///
+/// Replaces `overlay.sh`, all paths default to those of the Red Pitaya OS.
+pub struct Firmware {
+    bitstream: PathBuf,
+    dtbo: PathBuf,
+    xdevcfg: PathBuf,
+    overlays: PathBuf,
+    sysfs: Sysfs,
+    timeout: Duration,
+}
+
+impl Firmware {
+    /// Create a loader for `fpga.bit` and `fpga.dtbo` in `MERCURY_DIR`.
+    pub fn new() -> Self {
+        let dir = Path::new(MERCURY_DIR);
+        Firmware {
+            bitstream: dir.join("fpga.bit"),
+            dtbo: dir.join("fpga.dtbo"),
+            xdevcfg: PathBuf::from(XDEVCFG),
+            overlays: PathBuf::from(OVERLAYS),
+            sysfs: Sysfs::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Set the bitstream file.
+    pub fn set_bitstream<P: Into<PathBuf>>(&mut self, path: P) {
+        self.bitstream = path.into();
+    }
+
+    /// Set the device tree overlay file.
+    pub fn set_dtbo<P: Into<PathBuf>>(&mut self, path: P) {
+        self.dtbo = path.into();
+    }
+
+    /// Set the device node the bitstream is written to.
+    pub fn set_xdevcfg<P: Into<PathBuf>>(&mut self, path: P) {
+        self.xdevcfg = path.into();
+    }
+
+    /// Set the configfs directory of device tree overlays.
+    pub fn set_overlays<P: Into<PathBuf>>(&mut self, path: P) {
+        self.overlays = path.into();
+    }
+
+    /// Set the UIO devices waited for and verified after loading.
+    pub fn set_sysfs(&mut self, sysfs: Sysfs) {
+        self.sysfs = sysfs;
+    }
+
+    /// Set how long to wait for the UIO devices to appear.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Load the firmware and return the hardware id of the loaded design.
+    pub fn load(&self) -> Result<u32> {
+        self.load_bitstream()?;
+        hwid::invalidate_identity(&self.sysfs);
+        if self.remove_overlay()? {
+            self.wait_removed()?;
+        }
+        self.apply_overlay()?;
+        self.wait_devices()?;
+        self.verify()
+    }
+
+    /// Program the FPGA with the bitstream.
+    pub fn load_bitstream(&self) -> Result<()> {
+        let bitstream = read(&self.bitstream)?;
+        OpenOptions::new().write(true).open(&self.xdevcfg)
+            .and_then(|mut file| file.write_all(&bitstream))
+            .map_err(|err| Error::Firmware(self.xdevcfg.clone(), err))
+    }
+
+    /// Remove a previously applied mercury overlay.
+    ///
+    /// Returns `false` if no overlay was applied.
+    pub fn remove_overlay(&self) -> Result<bool> {
+        let path = self.overlays.join(OVERLAY_NAME);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_dir(&path).map_err(|err| Error::Firmware(path, err))?;
+        Ok(true)
+    }
+
+    /// Apply the mercury device tree overlay.
+    pub fn apply_overlay(&self) -> Result<()> {
+        let dtbo = read(&self.dtbo)?;
+        let path = self.overlays.join(OVERLAY_NAME);
+        fs::create_dir_all(&path).map_err(|err| Error::Firmware(path.clone(), err))?;
+        let path = path.join("dtbo");
+        fs::write(&path, &dtbo).map_err(|err| Error::Firmware(path, err))
+    }
+
+    /// Wait until the UIO devices of a removed overlay are gone from sysfs.
+    ///
+    /// Otherwise `wait_devices` could return before the devices of the new
+    /// overlay were probed.
+    pub fn wait_removed(&self) -> Result<()> {
+        // The UIO class directory is removed along with the last UIO device.
+        self.wait(|| self.sysfs.mercury_devices().map(|devices| devices.is_empty()).unwrap_or(true))
+    }
+
+    /// Wait until the UIO devices of the loaded design are listed in sysfs.
+    ///
+    /// Waits for `hwid` first, then for `ps2pl`, `mgmt` and the devices of
+    /// the modules the `BoardInfo` read from it reports.
+    pub fn wait_devices(&self) -> Result<()> {
+        // The UIO class directory only exists once a UIO device was probed.
+        self.wait(|| self.sysfs.find("hwid").is_ok())?;
+        hwid::invalidate_identity(&self.sysfs);
+        let board = BoardInfo::detect(&self.sysfs)?;
+        let mut names = vec!["hwid", "ps2pl", "mgmt"];
+        names.extend(board.modules().map(|module| module.uio_name()));
+        self.wait(|| match self.sysfs.mercury_devices() {
+            Ok(devices) => names.iter().all(|name| devices.iter().any(|info| info.name == *name)),
+            Err(_) => false,
+        })
+    }
+
+    fn wait<F: Fn() -> bool>(&self, done: F) -> Result<()> {
+        let start = Instant::now();
+        while !done() {
+            if start.elapsed() > self.timeout {
+                return Err(Error::Timeout(self.timeout));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    /// Check that the hardware id of the loaded design can be read and that
+    /// its git hash passes the compatibility check.
+    ///
+    /// Replaces the identity cached for the sysfs root by device constructors.
+    pub fn verify(&self) -> Result<u32> {
+        hwid::invalidate_identity(&self.sysfs);
+        compat::check(&self.sysfs)?;
+        Ok(hwid::identity(&self.sysfs)?.hwid)
+    }
+}
+
+impl Default for Firmware {
+    fn default() -> Self {
+        Firmware::new()
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|err| Error::Firmware(path.to_path_buf(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use fake::{FakeSysfs, HWID};
+    use sysfs::MERCURY_DEVICES;
+
+    /// Hardware id of a STEMlab 125-14 bitstream without lg and la.
+    const PARTIAL_HWID: u32 = 0x0f01;
+    /// Hardware id of a previously loaded bitstream leaving `hwid` unset.
+    const STALE_HWID: u32 = 0;
+
+    fn remove_devices(fake: &FakeSysfs) {
+        for num in 0..MERCURY_DEVICES.len() {
+            fake.remove_device(num);
+        }
+    }
+
+    fn firmware(fake: &FakeSysfs) -> Firmware {
+        let dir = fake.path();
+        fs::write(dir.join("fpga.bit"), b"bitstream").unwrap();
+        fs::write(dir.join("fpga.dtbo"), b"overlay").unwrap();
+        File::create(dir.join("xdevcfg")).unwrap();
+        fs::create_dir(dir.join("overlays")).unwrap();
+        let mut firmware = Firmware::new();
+        firmware.set_bitstream(dir.join("fpga.bit"));
+        firmware.set_dtbo(dir.join("fpga.dtbo"));
+        firmware.set_xdevcfg(dir.join("xdevcfg"));
+        firmware.set_overlays(dir.join("overlays"));
+        firmware.set_sysfs(fake.sysfs());
+        firmware.set_timeout(Duration::from_secs(5));
+        firmware
+    }
+
+    #[test]
+    fn load() {
+        let fake = FakeSysfs::new();
+        let firmware = firmware(&fake);
+        fake.add_mercury_devices(HWID);
+        assert_eq!(firmware.load().unwrap(), HWID);
+        assert_eq!(fs::read(fake.path().join("xdevcfg")).unwrap(), b"bitstream");
+        assert_eq!(fs::read(fake.path().join("overlays/mercury/dtbo")).unwrap(), b"overlay");
+    }
+
+    #[test]
+    fn reload() {
+        let fake = FakeSysfs::new();
+        let firmware = firmware(&fake);
+        let overlay = fake.path().join("overlays").join(OVERLAY_NAME);
+        fs::create_dir(&overlay).unwrap();
+        fake.add_mercury_devices(STALE_HWID);
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            // Act like the kernel, remove the devices of the stale overlay
+            // and probe them again once the new overlay is applied.
+            scope.spawn(|| {
+                while overlay.exists() && !done.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                thread::sleep(Duration::from_millis(50));
+                remove_devices(&fake);
+                while !overlay.join("dtbo").exists() && !done.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                fake.add_mercury_devices(HWID);
+            });
+            let result = firmware.load();
+            done.store(true, Ordering::SeqCst);
+            assert_eq!(result.unwrap(), HWID);
+        });
+    }
+
+    #[test]
+    fn partial_design() {
+        // Only the devices of the modules in the bitstream are waited for.
+        let fake = FakeSysfs::new();
+        let firmware = firmware(&fake);
+        fake.add_mercury_devices(PARTIAL_HWID);
+        assert!(fake.sysfs().find("la").is_err());
+        assert_eq!(firmware.load().unwrap(), PARTIAL_HWID);
+    }
+
+    #[test]
+    fn missing_devices() {
+        let fake = FakeSysfs::new();
+        let mut firmware = firmware(&fake);
+        firmware.set_timeout(Duration::from_millis(20));
+        match firmware.load() {
+            Err(Error::Timeout(_)) => {}
+            _ => panic!("loading without devices should time out"),
+        }
+    }
+}
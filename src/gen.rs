@@ -0,0 +1,442 @@
+use board::{BoardFamily, BoardInfo, Module};
+use burst::*;
+use compat::{self, Compat};
+use error::{Error, Result};
+use event::*;
+use gen_linear::*;
+use gen_table::*;
+use lock::{DeviceLock, LockMode, Shared};
+use sysfs::Sysfs;
+use uio::*;
+use volatile_register::RW;
+
+pub const BUFFER_SIZE: u32 = 0x4000; // 2**14 = 16384
+/// Sampling frequency of the STEMlab 125-14, see `BoardInfo`.
+pub const SAMPLING_FREQUENCY: u32 = 125_000_000;
+/// DAC resolution of the STEMlab 125-14 in bits, see `BoardInfo`.
+pub const DAC_BITS: u32 = 14;
+
+#[repr(C)]
+pub struct GenRegs {
+    event: EventRegs,
+    _rsv0: u32,
+    burst: BurstRegs,
+    linear: GenLinearRegs,
+    table: GenTableRegs,
+}
+
+impl Default for GenRegs {
+    /// Set registers to default (power-up) state.
+    fn default(&mut self) {
+        self.event.default();
+        self.burst.default();
+        self.linear.default();
+        self.table.default();
+    }
+}
+
+impl Show for GenRegs {
+    /// Print FPGA module registers.
+    fn show(&self) {
+        self.event.show();
+        self.burst.show();
+        self.linear.show();
+        self.table.show();
+    }
+}
+
+pub struct Generator {
+    regs: Box<dyn Mapping>,
+    buffer: Box<dyn Mapping>,
+    board: BoardInfo,
+    _lock: Option<DeviceLock>,
+    compat: Compat,
+}
+
+impl Generator {
+    /// Open generator channel `channel` (`0` or `1`), failing if another process is using it.
+    ///
+    /// Fails if the loaded bitstream has no generator for `channel`.
+    pub fn new(channel: usize) -> Result<Self> {
+        Generator::with_lock(channel, LockMode::Try)
+    }
+
+    /// Open generator channel `channel`, locking it against other processes with `mode`.
+    pub fn with_lock(channel: usize, mode: LockMode) -> Result<Self> {
+        Generator::with_sysfs(&Sysfs::new(), channel, mode)
+    }
+
+    /// Open channel `channel` of the device found through `sysfs`, locking it with `mode`.
+    pub fn with_sysfs(sysfs: &Sysfs, channel: usize, mode: LockMode) -> Result<Self> {
+        Generator::open(sysfs, channel, |uio_num| DeviceLock::acquire(sysfs, uio_num, mode))
+    }
+
+    /// Open generator channel `channel` read-only, sharing it with other read-only users.
+    ///
+    /// Fails with `Error::Busy` if another process holds an exclusive lock.
+    pub fn shared(channel: usize) -> Result<Shared<Self>> {
+        Generator::shared_with_sysfs(&Sysfs::new(), channel)
+    }
+
+    /// Open channel `channel` of the device found through `sysfs` read-only, see `shared`.
+    pub fn shared_with_sysfs(sysfs: &Sysfs, channel: usize) -> Result<Shared<Self>> {
+        Generator::open(sysfs, channel, |uio_num| DeviceLock::acquire_shared(sysfs, uio_num))
+            .map(Shared::new)
+    }
+
+    fn open<F>(sysfs: &Sysfs, channel: usize, lock: F) -> Result<Self>
+        where F: FnOnce(usize) -> Result<DeviceLock>
+    {
+        let compat = compat::check(sysfs)?;
+        let board = BoardInfo::detect(sysfs)?;
+        if let Some(module) = Module::gen(channel) {
+            board.require(module)?;
+        }
+        let uio = open_uio(sysfs, &format!("gen{}", channel))?;
+        let lock = lock(uio.num)?;
+        let regs = map_mapping(sysfs, &uio, 0)?;
+        let buffer = map_mapping(sysfs, &uio, 1)?;
+        let mut gen = Generator::from_mappings(regs, buffer)?;
+        gen.set_board(board);
+        gen._lock = Some(lock);
+        gen.compat = compat;
+        Ok(gen)
+    }
+
+    /// Create a generator backed by arbitrary register and buffer memory.
+    pub fn from_mappings(regs: Box<dyn Mapping>, buffer: Box<dyn Mapping>) -> Result<Self> {
+        check_size::<GenRegs>(&*regs)?;
+        check_size::<<Self as BufferDevice>::Buffer>(&*buffer)?;
+        let board = BoardInfo::with_family(BoardFamily::Stemlab125_14);
+        Ok(Generator { regs, buffer, board, _lock: None, compat: Compat::Unchecked })
+    }
+
+    /// Outcome of the compatibility check of the bitstream when the device
+    /// was opened, `Compat::Unchecked` for devices created from mappings.
+    pub fn compat(&self) -> Compat {
+        self.compat
+    }
+
+    /// Board the sample width and rate are taken from.
+    pub fn board(&self) -> BoardInfo {
+        self.board
+    }
+
+    /// Set the board the sample width and rate are taken from.
+    pub fn set_board(&mut self, board: BoardInfo) {
+        self.board = board;
+    }
+
+    /// Upload a waveform table of raw DAC samples.
+    ///
+    /// The table size is set to the length of the waveform.
+    pub fn set_waveform_raw(&mut self, waveform: &[i16]) -> Result<()> {
+        if waveform.is_empty() {
+            return Err(Error::EmptyBuffer);
+        }
+        if waveform.len() > BUFFER_SIZE as usize {
+            return Err(Error::BufferOverflow(waveform.len(), BUFFER_SIZE as usize));
+        }
+        for (reg, sample) in self.mut_buffer().iter_mut().zip(waveform) {
+            unsafe { reg.write(*sample); }
+        }
+        self.set_table_size((waveform.len() as u32) << TABLE_FRACTION_BITS)
+    }
+
+    /// Upload a waveform table normalized to the range `[-1.0, 1.0]`.
+    pub fn set_waveform(&mut self, waveform: &[f32]) -> Result<()> {
+        let max = ((1 << (self.board.dac_bits() - 1)) - 1) as f32;
+        let raw: Vec<i16> = waveform.iter()
+            .map(|v| (v.clamp(-1.0, 1.0) * max).round() as i16)
+            .collect();
+        self.set_waveform_raw(&raw)
+    }
+
+    /// Get output frequency in Hz.
+    pub fn frequency(&self) -> f32 {
+        self.table_step() as f32 / self.table_size() as f32 * self.board.sampling_frequency() as f32
+    }
+
+    /// Set output frequency in Hz.
+    ///
+    /// The frequency is the rate at which the whole waveform table is output.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        let step = self.table_size() as f64 * frequency as f64 / self.board.sampling_frequency() as f64;
+        self.set_table_step(step.round() as u32);
+    }
+
+    /// Get initial phase in degrees.
+    pub fn phase(&self) -> f32 {
+        self.table_offset() as f32 / self.table_size() as f32 * 360.0
+    }
+
+    /// Set initial phase in degrees.
+    pub fn set_phase(&mut self, phase: f32) {
+        let phase = ((phase % 360.0) + 360.0) % 360.0;
+        let offset = self.table_size() as f64 * phase as f64 / 360.0;
+        self.set_table_offset(offset as u32);
+    }
+
+    /// Get amplitude scaling factor.
+    pub fn amplitude(&self) -> f32 {
+        self.multiplier() as f32 / MULTIPLIER_UNITY as f32
+    }
+
+    /// Set amplitude scaling factor (`1.0` outputs the waveform unchanged).
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.set_multiplier((amplitude * MULTIPLIER_UNITY as f32).round() as i32);
+    }
+
+    /// Get offset relative to the DAC full scale.
+    pub fn offset(&self) -> f32 {
+        self.adder() as f32 / (1 << (self.board.dac_bits() - 1)) as f32
+    }
+
+    /// Set offset relative to the DAC full scale.
+    pub fn set_offset(&mut self, offset: f32) {
+        self.set_adder((offset * (1 << (self.board.dac_bits() - 1)) as f32).round() as i32);
+    }
+
+    /// Set the burst data length to `cycles` periods of the waveform.
+    ///
+    /// Requires the waveform and frequency to be set beforehand, fails with
+    /// `Error::InvalidValue` if no frequency is set or `cycles` is 0 or too
+    /// large.
+    pub fn set_burst_cycles(&mut self, cycles: u32) -> Result<()> {
+        let step = self.table_step() as u64;
+        if step == 0 {
+            return Err(Error::InvalidValue("table step", 0));
+        }
+        let len = cycles as u64 * self.table_size() as u64 / step;
+        if len == 0 || len > u32::MAX as u64 {
+            return Err(Error::InvalidValue("burst cycles", cycles));
+        }
+        self.set_burst_data_length(len as u32)
+    }
+
+    /// Returns the sample period of the DAC.
+    pub fn sample_period(&self) -> f32 {
+        1.0 / self.board.sampling_frequency() as f32
+    }
+}
+
+unsafe impl RegDevice for Generator {
+    type Registers = GenRegs;
+
+    fn reg_mmap(&self) -> &dyn Mapping {
+        &*self.regs
+    }
+}
+
+unsafe impl BufferDevice for Generator {
+    type Buffer = [RW<i16>; BUFFER_SIZE as usize];
+
+    fn buffer_mmap(&self) -> &dyn Mapping {
+        &*self.buffer
+    }
+}
+
+impl Default for Generator {
+    fn default(&mut self) {
+        self.mut_regs().default();
+    }
+}
+
+impl Show for Generator {
+    fn show(&self) {
+        self.regs().show();
+    }
+}
+
+impl EventRegsAPI for Generator {
+    fn reset(&mut self) {
+        self.mut_regs().event.reset();
+    }
+    fn start(&mut self) {
+        self.mut_regs().event.start();
+    }
+    fn stop(&mut self) {
+        self.mut_regs().event.stop();
+    }
+    fn trigger(&mut self) {
+        self.mut_regs().event.trigger();
+    }
+    fn start_trigger(&mut self) {
+        self.mut_regs().event.start_trigger();
+    }
+    fn is_running(&self) -> bool {
+        self.regs().event.is_running()
+    }
+    fn is_triggered(&self) -> bool {
+        self.regs().event.is_triggered()
+    }
+    fn sync_source(&self) -> Result<SyncSource> {
+        self.regs().event.sync_source()
+    }
+    fn set_sync_source(&mut self, event: SyncSource) {
+        self.mut_regs().event.set_sync_source(event);
+    }
+    fn trigger_source(&self) -> TriggerSources {
+        self.regs().event.trigger_source()
+    }
+    fn set_trigger_source<T: Into<TriggerSources>>(&mut self, trigger: T) {
+        self.mut_regs().event.set_trigger_source(trigger);
+    }
+}
+
+impl BurstRegsAPI for Generator {
+    fn burst(&self) -> bool {
+        self.regs().burst.burst()
+    }
+    fn set_burst(&mut self, burst: bool) {
+        self.mut_regs().burst.set_burst(burst);
+    }
+    fn burst_infinite(&self) -> bool {
+        self.regs().burst.burst_infinite()
+    }
+    fn set_burst_infinite(&mut self, infinite: bool) {
+        self.mut_regs().burst.set_burst_infinite(infinite);
+    }
+    fn burst_data_length(&self) -> u32 {
+        self.regs().burst.burst_data_length()
+    }
+    fn set_burst_data_length(&mut self, len: u32) -> Result<()> {
+        self.mut_regs().burst.set_burst_data_length(len)
+    }
+    fn burst_period_length(&self) -> u32 {
+        self.regs().burst.burst_period_length()
+    }
+    fn set_burst_period_length(&mut self, len: u32) -> Result<()> {
+        self.mut_regs().burst.set_burst_period_length(len)
+    }
+    fn burst_repetitions(&self) -> u32 {
+        self.regs().burst.burst_repetitions()
+    }
+    fn set_burst_repetitions(&mut self, num: u32) -> Result<()> {
+        self.mut_regs().burst.set_burst_repetitions(num)
+    }
+    fn burst_length_status(&self) -> u32 {
+        self.regs().burst.burst_length_status()
+    }
+    fn burst_repetitions_status(&self) -> u32 {
+        self.regs().burst.burst_repetitions_status()
+    }
+}
+
+impl GenLinearRegsAPI for Generator {
+    fn multiplier(&self) -> i32 {
+        self.regs().linear.multiplier()
+    }
+    fn set_multiplier(&mut self, mul: i32) {
+        self.mut_regs().linear.set_multiplier(mul);
+    }
+    fn adder(&self) -> i32 {
+        self.regs().linear.adder()
+    }
+    fn set_adder(&mut self, sum: i32) {
+        self.mut_regs().linear.set_adder(sum);
+    }
+}
+
+impl GenTableRegsAPI for Generator {
+    fn table_size(&self) -> u32 {
+        self.regs().table.table_size()
+    }
+    fn set_table_size(&mut self, size: u32) -> Result<()> {
+        self.mut_regs().table.set_table_size(size)
+    }
+    fn table_offset(&self) -> u32 {
+        self.regs().table.table_offset()
+    }
+    fn set_table_offset(&mut self, offset: u32) {
+        self.mut_regs().table.set_table_offset(offset);
+    }
+    fn table_step(&self) -> u32 {
+        self.regs().table.table_step()
+    }
+    fn set_table_step(&mut self, step: u32) {
+        self.mut_regs().table.set_table_step(step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error::Error;
+    use fake::{self, FakeMemory, FakeSysfs};
+    use super::*;
+
+    fn generator() -> Generator {
+        Generator::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                 Box::new(FakeMemory::new(BUFFER_SIZE as usize * 2))).unwrap()
+    }
+
+    #[test]
+    fn waveform() {
+        let mut gen = generator();
+        gen.set_waveform(&[0.0, 1.0, 0.0, -1.0]).unwrap();
+        assert_eq!(fake::read_u16(gen.buffer_mmap(), 2), 8191);
+        assert_eq!(fake::read_u16(gen.buffer_mmap(), 6) as i16, -8191);
+        assert_eq!(fake::read(gen.reg_mmap(), 0x30), (4 << TABLE_FRACTION_BITS) - 1);
+        match gen.set_waveform_raw(&[]) {
+            Err(Error::EmptyBuffer) => {}
+            _ => panic!("empty waveform should be rejected"),
+        }
+        assert!(gen.set_waveform_raw(&[0; BUFFER_SIZE as usize + 1]).is_err());
+    }
+
+    #[test]
+    fn frequency_and_phase() {
+        let mut gen = generator();
+        gen.set_waveform(&[0.0; 4]).unwrap();
+        gen.set_frequency(1_000_000.0);
+        assert!((gen.frequency() - 1_000_000.0).abs() < 500.0);
+        gen.set_phase(90.0);
+        assert!((gen.phase() - 90.0).abs() < 0.01);
+        gen.set_amplitude(0.5);
+        assert_eq!(fake::read(gen.reg_mmap(), 0x28), 1 << 13);
+    }
+
+    #[test]
+    fn burst() {
+        let mut gen = generator();
+        gen.set_burst(true);
+        gen.set_burst_infinite(true);
+        assert_eq!(fake::read(gen.reg_mmap(), 0x10), 3);
+        assert!(gen.set_burst_data_length(0).is_err());
+        assert!(gen.set_burst_period_length(0).is_err());
+        assert!(gen.set_burst_repetitions(0).is_err());
+        gen.set_burst_repetitions(3).unwrap();
+        assert_eq!(gen.burst_repetitions(), 3);
+    }
+
+    #[test]
+    fn burst_cycles() {
+        let mut gen = generator();
+        gen.set_waveform(&[0.0; 4]).unwrap();
+        assert!(gen.set_burst_cycles(2).is_err());
+        gen.set_frequency(1_000_000.0);
+        gen.set_burst_cycles(2).unwrap();
+        assert_eq!(gen.burst_data_length(), 250);
+        assert!(gen.set_burst_cycles(0).is_err());
+    }
+
+    #[test]
+    fn lock() {
+        let fake = FakeSysfs::new();
+        fake.add_mercury_devices(fake::HWID);
+        let sysfs = fake.sysfs();
+        let gen = Generator::with_sysfs(&sysfs, 0, LockMode::Try).unwrap();
+        match Generator::with_sysfs(&sysfs, 0, LockMode::Try) {
+            Err(Error::Busy(..)) => {}
+            _ => panic!("second exclusive open should fail"),
+        }
+        assert!(Generator::shared_with_sysfs(&sysfs, 0).is_err());
+        // The other channel is a separate device.
+        Generator::with_sysfs(&sysfs, 1, LockMode::Try).unwrap();
+        drop(gen);
+        let first = Generator::shared_with_sysfs(&sysfs, 0).unwrap();
+        let second = Generator::shared_with_sysfs(&sysfs, 0).unwrap();
+        assert_eq!(first.board(), second.board());
+        assert!(Generator::with_sysfs(&sysfs, 0, LockMode::Try).is_err());
+    }
+}
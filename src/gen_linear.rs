@@ -0,0 +1,55 @@
+use uio::*;
+use volatile_register::RW;
+
+/// Multiplier value representing a gain of 1.0.
+pub const MULTIPLIER_UNITY: i32 = 1 << 14;
+
+#[repr(C)]
+pub struct GenLinearRegs {
+    /// Multiplier (amplitude)
+    cfg_mul: RW<i32>,
+    /// Adder (offset)
+    cfg_sum: RW<i32>,
+}
+
+impl Default for GenLinearRegs {
+    fn default(&mut self) {
+        unsafe {
+            self.cfg_mul.write(MULTIPLIER_UNITY);
+            self.cfg_sum.write(0);
+        }
+    }
+}
+
+impl Show for GenLinearRegs {
+    fn show(&self) {
+        println!("cfg_mul = {:x}", self.cfg_mul.read());
+        println!("cfg_sum = {:x}", self.cfg_sum.read());
+    }
+}
+
+pub trait GenLinearRegsAPI {
+    /// Get output multiplier (`MULTIPLIER_UNITY` is a gain of 1.0).
+    fn multiplier(&self) -> i32;
+    /// Set output multiplier.
+    fn set_multiplier(&mut self, mul: i32);
+    /// Get output offset in raw DAC counts.
+    fn adder(&self) -> i32;
+    /// Set output offset in raw DAC counts.
+    fn set_adder(&mut self, sum: i32);
+}
+
+impl GenLinearRegsAPI for GenLinearRegs {
+    fn multiplier(&self) -> i32 {
+        self.cfg_mul.read()
+    }
+    fn set_multiplier(&mut self, mul: i32) {
+        unsafe { self.cfg_mul.write(mul); }
+    }
+    fn adder(&self) -> i32 {
+        self.cfg_sum.read()
+    }
+    fn set_adder(&mut self, sum: i32) {
+        unsafe { self.cfg_sum.write(sum); }
+    }
+}
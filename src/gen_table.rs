@@ -0,0 +1,78 @@
+use error::{Error, Result};
+use uio::*;
+use volatile_register::RW;
+
+/// Number of fractional bits of the table size, offset and step.
+pub const TABLE_FRACTION_BITS: u32 = 16;
+
+#[repr(C)]
+pub struct GenTableRegs {
+    /// Table size
+    cfg_siz: RW<u32>,
+    /// Table address offset
+    cfg_off: RW<u32>,
+    /// Table address step
+    cfg_ste: RW<u32>,
+}
+
+impl Default for GenTableRegs {
+    fn default(&mut self) {
+        unsafe {
+            self.cfg_siz.write(0);
+            self.cfg_off.write(0);
+            self.cfg_ste.write(0);
+        }
+    }
+}
+
+impl Show for GenTableRegs {
+    fn show(&self) {
+        println!("cfg_siz = {:x}", self.cfg_siz.read());
+        println!("cfg_off = {:x}", self.cfg_off.read());
+        println!("cfg_ste = {:x}", self.cfg_ste.read());
+    }
+}
+
+/// All values are fixed point numbers with `TABLE_FRACTION_BITS`
+/// fractional bits.
+pub trait GenTableRegsAPI {
+    /// Get table size.
+    fn table_size(&self) -> u32;
+    /// Set table size.
+    ///
+    /// Fails with `Error::InvalidValue` if `size` is 0.
+    fn set_table_size(&mut self, size: u32) -> Result<()>;
+    /// Get initial table address offset (phase).
+    fn table_offset(&self) -> u32;
+    /// Set initial table address offset (phase).
+    fn set_table_offset(&mut self, offset: u32);
+    /// Get table address step (frequency).
+    fn table_step(&self) -> u32;
+    /// Set table address step (frequency).
+    fn set_table_step(&mut self, step: u32);
+}
+
+impl GenTableRegsAPI for GenTableRegs {
+    fn table_size(&self) -> u32 {
+        self.cfg_siz.read().wrapping_add(1)
+    }
+    fn set_table_size(&mut self, size: u32) -> Result<()> {
+        if size == 0 {
+            return Err(Error::InvalidValue("table size", size));
+        }
+        unsafe { self.cfg_siz.write(size - 1); }
+        Ok(())
+    }
+    fn table_offset(&self) -> u32 {
+        self.cfg_off.read()
+    }
+    fn set_table_offset(&mut self, offset: u32) {
+        unsafe { self.cfg_off.write(offset); }
+    }
+    fn table_step(&self) -> u32 {
+        self.cfg_ste.read()
+    }
+    fn set_table_step(&mut self, step: u32) {
+        unsafe { self.cfg_ste.write(step); }
+    }
+}
@@ -1,3 +1,8 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use board::BoardInfo;
+use error::Result;
+use sysfs::Sysfs;
 use uio::*;
 use volatile_register::RO;
 
@@ -13,15 +18,54 @@ pub struct HwIdRegs {
 }
 
 pub struct HwId {
-    regs: MemoryMap,
+    regs: Box<dyn Mapping>,
+}
+
+/// Hardware id and git hash identifying a loaded bitstream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub hwid: u32,
+    pub gith: String,
+}
+
+/// Identities read by `identity`, by sysfs root.
+static IDENTITIES: Mutex<Vec<(PathBuf, Identity)>> = Mutex::new(Vec::new());
+
+/// Identity of the bitstream loaded in the device tree of `sysfs`.
+///
+/// The hwid device is only read the first time, later calls for the same
+/// sysfs root return the cached identity until `invalidate_identity`.
+pub fn identity(sysfs: &Sysfs) -> Result<Identity> {
+    let mut identities = IDENTITIES.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((_, identity)) = identities.iter().find(|(root, _)| root == sysfs.root()) {
+        return Ok(identity.clone());
+    }
+    let identity = HwId::with_sysfs(sysfs)?.identity();
+    identities.push((sysfs.root().to_path_buf(), identity.clone()));
+    Ok(identity)
+}
+
+/// Forget the cached identity of `sysfs`, e.g. after loading a bitstream.
+pub fn invalidate_identity(sysfs: &Sysfs) {
+    let mut identities = IDENTITIES.lock().unwrap_or_else(|err| err.into_inner());
+    identities.retain(|(root, _)| root != sysfs.root());
 }
 
 impl HwId {
-    pub fn new() -> Self {
-        let uio_num = get_uio_num("/dev/uio/hwid");
-        let uio = UioDevice::new(uio_num).unwrap();
-        let regs = uio.map_mapping(0).unwrap();
-        HwId { regs }
+    pub fn new() -> Result<Self> {
+        HwId::with_sysfs(&Sysfs::new())
+    }
+
+    /// Open the hwid device found through `sysfs`.
+    pub fn with_sysfs(sysfs: &Sysfs) -> Result<Self> {
+        let uio = open_uio(sysfs, "hwid")?;
+        HwId::from_mapping(map_mapping(sysfs, &uio, 0)?)
+    }
+
+    /// Create a hwid device backed by arbitrary register memory.
+    pub fn from_mapping(regs: Box<dyn Mapping>) -> Result<Self> {
+        check_size::<HwIdRegs>(&*regs)?;
+        Ok(HwId { regs })
     }
 
     /// Red Pitaya FPGA identification number (32bit).
@@ -29,6 +73,16 @@ impl HwId {
         self.regs().hwid.read()
     }
 
+    /// Board and FPGA variant encoded in the hardware id.
+    pub fn board(&self) -> BoardInfo {
+        BoardInfo::from_hwid(self.hwid())
+    }
+
+    /// Hardware id and git hash.
+    pub fn identity(&self) -> Identity {
+        Identity { hwid: self.hwid(), gith: self.gith() }
+    }
+
     /// Zynq FPGA efuse (32bit).
     pub fn efuse(&self) -> u32 {
         self.regs().efuse.read()
@@ -56,19 +110,67 @@ impl HwId {
     }
 }
 
-impl RegDevice for HwId {
+unsafe impl RegDevice for HwId {
     type Registers = HwIdRegs;
 
-    fn reg_mmap(&self) -> &MemoryMap {
-        &self.regs
+    fn reg_mmap(&self) -> &dyn Mapping {
+        &*self.regs
     }
 }
 
 impl Show for HwId {
     fn show(&self) {
         println!("hwid = {:x}", self.hwid());
+        println!("board = {:?}", self.board().family());
         println!("efuse = {:x}", self.efuse());
         println!("dna = {:x}", self.dna());
         println!("gith = {}", self.gith());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use fake::{self, FakeMemory, FakeSysfs};
+    use super::*;
+
+    #[test]
+    fn identification() {
+        let regs = FakeMemory::new(0x40);
+        fake::write(&regs, 0x00, 0x1234);
+        fake::write(&regs, 0x08, 0xcafe);
+        fake::write(&regs, 0x10, 0x89abcdef);
+        fake::write(&regs, 0x14, 0x01234567);
+        for i in 0..5 {
+            fake::write(&regs, 0x20 + i * 4, 0x11111111 * (i as u32 + 1));
+        }
+        let hwid = HwId::from_mapping(Box::new(regs)).unwrap();
+        assert_eq!(hwid.hwid(), 0x1234);
+        assert_eq!(hwid.efuse(), 0xcafe);
+        assert_eq!(hwid.dna(), 0x0123456789abcdef);
+        assert_eq!(hwid.gith(), "5555555544444444333333332222222211111111");
+    }
+
+    #[test]
+    fn cached_identity() {
+        let fake = FakeSysfs::new();
+        fake.add_device(3, "hwid", &[0x1000]);
+        let write_hwid = |hwid: u32| {
+            OpenOptions::new().write(true).open(fake.sysfs().dev_path(3))
+                .and_then(|mut file| file.write_all(&hwid.to_le_bytes()))
+                .unwrap();
+        };
+        write_hwid(0);
+        assert_eq!(identity(&fake.sysfs()).unwrap().hwid, 0);
+        write_hwid(1);
+        assert_eq!(identity(&fake.sysfs()).unwrap().hwid, 0);
+        invalidate_identity(&fake.sysfs());
+        assert_eq!(identity(&fake.sysfs()).unwrap().hwid, 1);
+    }
+
+    #[test]
+    fn mapping_size() {
+        assert!(HwId::from_mapping(Box::new(FakeMemory::new(0x20))).is_err());
+    }
+}
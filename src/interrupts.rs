@@ -1,27 +1,108 @@
-use uio::*;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+use error::{Error, Result};
+use libc;
+use sysfs::Sysfs;
+use uio::IrqAPI;
 
+/// Interrupt raised by the FPGA modules through the `ps2pl` device.
+///
+/// The interrupt is disabled by the kernel once it fired and has to be
+/// enabled again before waiting for the next one. It is shared by all
+/// modules, check the status of a module after waking up.
 pub struct Interrupt {
-    uio: UioDevice,
-    regs: MemoryMap,
+    uio_num: usize,
+    file: File,
 }
 
 impl Interrupt {
-    pub fn new() -> Self {
-        let uio_num = get_uio_num("/dev/uio/ps2pl");
-        let uio = UioDevice::new(uio_num).unwrap();
-        let regs = uio.map_mapping(0).unwrap();
-        Interrupt { uio, regs }
+    pub fn new() -> Result<Self> {
+        Interrupt::with_sysfs(&Sysfs::new())
     }
 
-    pub fn enable(&mut self) {
-        self.uio.irq_enable().unwrap();
+    /// Open the interrupt of the `ps2pl` device found through `sysfs`.
+    pub fn with_sysfs(sysfs: &Sysfs) -> Result<Self> {
+        let uio_num = sysfs.find("ps2pl")?.num;
+        match OpenOptions::new().read(true).write(true).open(sysfs.dev_path(uio_num)) {
+            Ok(file) => Ok(Interrupt { uio_num, file }),
+            Err(err) => Err(Error::Open(uio_num, err)),
+        }
     }
 
-    pub fn disable(&mut self) {
-        self.uio.irq_disable().unwrap();
+    /// Interrupt backed by an arbitrary file, e.g. one end of a socket pair.
+    #[cfg(all(test, feature = "async"))]
+    pub(crate) fn from_file(uio_num: usize, file: File) -> Self {
+        Interrupt { uio_num, file }
     }
 
-    pub fn wfi(&mut self) -> u32 {
-        self.uio.irq_wait().unwrap()
+    /// Number of the UIO device.
+    pub fn uio_num(&self) -> usize {
+        self.uio_num
+    }
+
+    /// Read the interrupt count, fails with `WouldBlock` in non-blocking mode.
+    pub(crate) fn read_count(&mut self) -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.file.read_exact(&mut bytes)?;
+        Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 |
+           (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+    }
+
+    fn control(&mut self, enable: bool) -> Result<()> {
+        let value = enable as u32;
+        let bytes = [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+        self.file.write_all(&bytes).map_err(|err| Error::Irq(self.uio_num, err))
+    }
+
+    pub fn enable(&mut self) -> Result<()> {
+        self.control(true)
+    }
+
+    pub fn disable(&mut self) -> Result<()> {
+        self.control(false)
+    }
+
+    /// Wait for interrupt.
+    ///
+    /// Returns the total number of interrupts.
+    pub fn wfi(&mut self) -> Result<u32> {
+        let uio_num = self.uio_num;
+        self.read_count().map_err(|err| Error::Irq(uio_num, err))
+    }
+
+    /// Wait for interrupt at most `timeout`.
+    ///
+    /// Returns `None` if the timeout elapsed without an interrupt.
+    pub fn wfi_timeout(&mut self, timeout: Duration) -> Result<Option<u32>> {
+        let millis = timeout.as_nanos().div_ceil(1_000_000);
+        let mut fds = libc::pollfd { fd: self.file.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let res = unsafe { libc::poll(&mut fds, 1, millis.min(i32::MAX as u128) as i32) };
+        if res < 0 {
+            return Err(Error::Irq(self.uio_num, io::Error::last_os_error()));
+        }
+        if res == 0 {
+            return Ok(None);
+        }
+        self.wfi().map(Some)
+    }
+}
+
+impl IrqAPI for Interrupt {
+    fn enable_irq(&mut self) -> Result<()> {
+        self.enable()
+    }
+    fn disable_irq(&mut self) -> Result<()> {
+        self.disable()
+    }
+    fn wait_irq(&mut self) -> Result<u32> {
+        self.wfi()
+    }
+}
+
+impl AsRawFd for Interrupt {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
     }
 }
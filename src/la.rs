@@ -1,12 +1,28 @@
-use acquire::*;
+use std::cmp;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+use acquire::{self, *};
+use board::{BoardFamily, BoardInfo, Module};
+use compat::{self, Compat};
+use error::{Error, Result};
 use event::*;
+use interrupts::Interrupt;
+use la_decode::Decoder;
 use la_mask::*;
-use la_rle::*;
+use la_measure::{self, Measurements, Stats};
+use la_rle::{self, *};
 use la_trigger::*;
+use lock::{DeviceLock, LockMode, Shared};
+use osc_trigger::Edge;
+use sysfs::Sysfs;
 use uio::*;
 use volatile_register::RO;
 
 pub const BUFFER_SIZE: u32 = 0x4000; // 2**14 = 16384
+/// Number of 32bit RLE words fitting into the buffer.
+pub const RLE_BUFFER_SIZE: u32 = BUFFER_SIZE / 2;
+/// Sampling frequency of the STEMlab 125-14, see `BoardInfo`.
 pub const SAMPLING_FREQUENCY: u32 = 125_000_000;
 
 #[repr(C)]
@@ -43,22 +59,147 @@ impl Show for LaRegs {
 }
 
 pub struct LogicAnalyzer {
-    regs: MemoryMap,
-    buffer: MemoryMap,
+    regs: Box<dyn Mapping>,
+    buffer: Box<dyn Mapping>,
+    irq: Option<Interrupt>,
+    _lock: Option<DeviceLock>,
+    compat: Compat,
+    board: BoardInfo,
 }
 
 impl LogicAnalyzer {
-    pub fn new() -> Self {
-        let uio_num = get_uio_num("/dev/uio/la");
-        let uio = UioDevice::new(uio_num).unwrap();
-        let regs = uio.map_mapping(0).unwrap();
-        let buffer = uio.map_mapping(1).unwrap();
-        LogicAnalyzer { regs, buffer }
+    /// Open the logic analyzer, failing if another process is using it.
+    pub fn new() -> Result<Self> {
+        LogicAnalyzer::with_lock(LockMode::Try)
+    }
+
+    /// Open the logic analyzer, locking it against other processes with `mode`.
+    pub fn with_lock(mode: LockMode) -> Result<Self> {
+        LogicAnalyzer::with_sysfs(&Sysfs::new(), mode)
+    }
+
+    /// Open the logic analyzer found through `sysfs`, locking it with `mode`.
+    pub fn with_sysfs(sysfs: &Sysfs, mode: LockMode) -> Result<Self> {
+        LogicAnalyzer::open(sysfs, |uio_num| DeviceLock::acquire(sysfs, uio_num, mode))
+    }
+
+    /// Open the logic analyzer read-only, sharing it with other read-only users.
+    ///
+    /// Fails with `Error::Busy` if another process holds an exclusive lock.
+    pub fn shared() -> Result<Shared<Self>> {
+        LogicAnalyzer::shared_with_sysfs(&Sysfs::new())
+    }
+
+    /// Open the logic analyzer found through `sysfs` read-only, see `shared`.
+    pub fn shared_with_sysfs(sysfs: &Sysfs) -> Result<Shared<Self>> {
+        LogicAnalyzer::open(sysfs, |uio_num| DeviceLock::acquire_shared(sysfs, uio_num))
+            .map(Shared::new)
+    }
+
+    fn open<F: FnOnce(usize) -> Result<DeviceLock>>(sysfs: &Sysfs, lock: F) -> Result<Self> {
+        let compat = compat::check(sysfs)?;
+        let board = BoardInfo::detect(sysfs)?;
+        board.require(Module::La)?;
+        let uio = open_uio(sysfs, "la")?;
+        let lock = lock(uio.num)?;
+        let regs = map_mapping(sysfs, &uio, 0)?;
+        let buffer = map_mapping(sysfs, &uio, 1)?;
+        let mut la = LogicAnalyzer::from_mappings(regs, buffer)?;
+        la.irq = Some(Interrupt::with_sysfs(sysfs)?);
+        la._lock = Some(lock);
+        la.compat = compat;
+        la.set_board(board);
+        Ok(la)
+    }
+
+    /// Create a logic analyzer backed by arbitrary register and buffer memory.
+    ///
+    /// The logic analyzer has no interrupt, `capture` polls the run status.
+    /// Assumes a STEMlab 125-14, see `set_board`.
+    pub fn from_mappings(regs: Box<dyn Mapping>, buffer: Box<dyn Mapping>) -> Result<Self> {
+        check_size::<LaRegs>(&*regs)?;
+        check_size::<<Self as BufferDevice>::Buffer>(&*buffer)?;
+        let board = BoardInfo::with_family(BoardFamily::Stemlab125_14);
+        Ok(LogicAnalyzer {
+            regs, buffer, irq: None, _lock: None, compat: Compat::Unchecked, board,
+        })
+    }
+
+    /// Outcome of the compatibility check of the bitstream when the device
+    /// was opened, `Compat::Unchecked` for devices created from mappings.
+    pub fn compat(&self) -> Compat {
+        self.compat
+    }
+
+    /// Board the sample rate is taken from.
+    pub fn board(&self) -> BoardInfo {
+        self.board
+    }
+
+    /// Set the board the sample rate is taken from.
+    pub fn set_board(&mut self, board: BoardInfo) {
+        self.board = board;
+    }
+
+    /// Take the `ps2pl` interrupt to wait for it separately, e.g. with `AsyncIrq`.
+    ///
+    /// Afterwards `capture` polls the run status.
+    pub fn take_irq(&mut self) -> Option<Interrupt> {
+        self.irq.take()
+    }
+
+    /// Start an acquisition and wait until it completes.
+    ///
+    /// The acquisition waits for an event of the configured trigger sources.
+    /// Sleeps on the `ps2pl` interrupt until the run status reports the
+    /// acquisition stopped and returns the acquired samples in chronological
+    /// order. The acquisition is stopped if it didn't complete within `timeout`.
+    pub fn capture(&mut self, timeout: Duration) -> Result<Vec<u16>> {
+        self.acquire(false, timeout)
+    }
+
+    /// Start an acquisition, trigger it immediately and wait until it completes.
+    ///
+    /// Like `start_trigger` this doesn't wait for the trigger sources,
+    /// otherwise it behaves like `capture`.
+    pub fn capture_now(&mut self, timeout: Duration) -> Result<Vec<u16>> {
+        self.acquire(true, timeout)
+    }
+
+    fn acquire(&mut self, trigger: bool, timeout: Duration) -> Result<Vec<u16>> {
+        let deadline = Instant::now() + timeout;
+        self.reset();
+        if trigger {
+            self.start_trigger();
+        } else {
+            self.start();
+        }
+        loop {
+            // Enable the interrupt before checking the status, so a
+            // completion right after the check still wakes us up.
+            if let Some(ref mut irq) = self.irq {
+                irq.enable()?;
+            }
+            if !self.is_running() {
+                return Ok(self.data(self.available()));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                self.stop();
+                return Err(Error::Timeout(timeout));
+            }
+            match self.irq {
+                Some(ref mut irq) => {
+                    irq.wfi_timeout(deadline - now)?;
+                }
+                None => thread::sleep(cmp::min(deadline - now, Duration::from_millis(1))),
+            }
+        }
     }
 
     /// Returns the sample rate depending on decimation factor.
     pub fn sample_rate(&self) -> f32 {
-        SAMPLING_FREQUENCY as f32 / self.decimation() as f32
+        self.board.sampling_frequency() as f32 / self.decimation() as f32
     }
 
     /// Returns the sample period depending on decimation factor.
@@ -66,38 +207,117 @@ impl LogicAnalyzer {
         1.0 / self.sample_rate()
     }
 
-    /*
     /// Mask out overflow bit and sum pre and post trigger counters.
+    ///
+    /// Returns the position in the buffer the next sample will be written to.
     pub fn pointer(&self) -> usize {
-        let count = self.trigger_pre_status() + self.trigger_post_status();
-        let address = count as usize % BUFFER_SIZE;
-        address
+        acquire::pointer(self, BUFFER_SIZE as usize)
+    }
+
+    /// Number of valid samples stored in the buffer.
+    ///
+    /// Once the buffer wrapped around all `BUFFER_SIZE` samples are valid.
+    pub fn available(&self) -> usize {
+        acquire::available(self, BUFFER_SIZE as usize)
     }
 
     /// Read data buffer.
-    /// num_samples : Number of samples to read from FPGA buffer.
-    /// Returns array containing binary samples. The data is aligned to the end
-    /// of the last sample stored in the buffer.
-    pub fn data(&self, num_samples: usize) -> Iterator<u16> {
-        let ptr = self.pointer();
-        let address = (BUFFER_SIZE + ptr - num_samples) % BUFFER_SIZE;
+    ///
+    /// num_samples : Number of samples to read from FPGA buffer, at most
+    /// `available()` samples are returned.
+    /// Returns the binary samples in chronological order (oldest first).
+    /// The data is aligned to the end of the last sample stored in the buffer.
+    pub fn data(&self, num_samples: usize) -> Vec<u16> {
+        let buffer = self.buffer();
+        acquire::indices(self, BUFFER_SIZE as usize, num_samples)
+            .map(|i| buffer[i].read())
+            .collect()
+    }
+
+    /// Read RLE compressed data buffer.
+    ///
+    /// Returns the `counter_last()` RLE words of the last stream in
+    /// chronological order. The stream is written from the start of the
+    /// buffer, if it was longer than the buffer it wrapped around and only
+    /// the last `RLE_BUFFER_SIZE` words are returned.
+    pub fn rle_words(&self) -> Vec<u32> {
+        let words = self.buffer_mmap().data() as *const u32;
+        let size = RLE_BUFFER_SIZE as usize;
+        let count = self.counter_last() as usize;
+        let num = cmp::min(count, size);
+        let start = (count - num) % size;
+        (0..num)
+            .map(|i| unsafe { ptr::read_volatile(words.add((start + i) % size)) })
+            .collect()
+    }
+
+    /// Read and decode RLE compressed data buffer.
+    ///
+    /// Use `la_rle::expand` to get the individual samples.
+    pub fn rle_data(&self) -> Vec<Run> {
+        la_rle::decode(&self.rle_words())
+    }
+
+    /// Run `decoder` over `samples` captured with the current sample rate.
+    pub fn decode<D: Decoder>(&self, decoder: &D, samples: &[u16]) -> D::Output {
+        decoder.decode(samples, self.sample_rate())
+    }
+
+    /// Measure `channel` of `samples` captured with the current sample rate.
+    pub fn measure(&self, samples: &[u16], channel: usize) -> Result<Measurements> {
+        la_measure::measure(samples, channel, self.sample_period())
+    }
+
+    /// Measure the delay between edges of two channels of `samples` captured
+    /// with the current sample rate, see `la_measure::delay`.
+    pub fn delay(&self, samples: &[u16], from: usize, from_edge: Edge, to: usize, to_edge: Edge)
+                 -> Result<Option<Stats>> {
+        la_measure::delay(samples, from, from_edge, to, to_edge, self.sample_period())
+    }
+
+    /// Position of the trigger sample in the array returned by `data`.
+    ///
+    /// Returns `None` if no trigger occurred or the trigger sample is not
+    /// part of the last `num_samples` samples.
+    pub fn trigger_position(&self, num_samples: usize) -> Option<usize> {
+        acquire::trigger_position(self, self.is_triggered(), BUFFER_SIZE as usize, num_samples)
     }
-    */
 }
 
-impl RegDevice for LogicAnalyzer {
+unsafe impl RegDevice for LogicAnalyzer {
     type Registers = LaRegs;
 
-    fn reg_mmap(&self) -> &MemoryMap {
-        &self.regs
+    fn reg_mmap(&self) -> &dyn Mapping {
+        &*self.regs
     }
 }
 
-impl BufferDevice for LogicAnalyzer {
+unsafe impl BufferDevice for LogicAnalyzer {
     type Buffer = [RO<u16>; BUFFER_SIZE as usize];
 
-    fn buffer_mmap(&self) -> &MemoryMap {
-        &self.buffer
+    fn buffer_mmap(&self) -> &dyn Mapping {
+        &*self.buffer
+    }
+}
+
+impl IrqAPI for LogicAnalyzer {
+    fn enable_irq(&mut self) -> Result<()> {
+        match self.irq {
+            Some(ref mut irq) => irq.enable(),
+            None => Err(Error::NoIrq),
+        }
+    }
+    fn disable_irq(&mut self) -> Result<()> {
+        match self.irq {
+            Some(ref mut irq) => irq.disable(),
+            None => Err(Error::NoIrq),
+        }
+    }
+    fn wait_irq(&mut self) -> Result<u32> {
+        match self.irq {
+            Some(ref mut irq) => irq.wfi(),
+            None => Err(Error::NoIrq),
+        }
     }
 }
 
@@ -135,16 +355,16 @@ impl EventRegsAPI for LogicAnalyzer {
     fn is_triggered(&self) -> bool {
         self.regs().event.is_triggered()
     }
-    fn sync_source(&self) -> SyncSource {
+    fn sync_source(&self) -> Result<SyncSource> {
         self.regs().event.sync_source()
     }
     fn set_sync_source(&mut self, event: SyncSource) {
         self.mut_regs().event.set_sync_source(event);
     }
-    fn trigger_source(&self) -> TriggerSource {
+    fn trigger_source(&self) -> TriggerSources {
         self.regs().event.trigger_source()
     }
-    fn set_trigger_source(&mut self, trigger: TriggerSource) {
+    fn set_trigger_source<T: Into<TriggerSources>>(&mut self, trigger: T) {
         self.mut_regs().event.set_trigger_source(trigger);
     }
 }
@@ -232,3 +452,168 @@ impl LaTriggerRegsAPI for LogicAnalyzer {
         self.mut_regs().trigger.set_neg_edge_trigger_mask(mask);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use fake::{self, FakeMemory};
+    use super::*;
+
+    fn logic_analyzer() -> LogicAnalyzer {
+        LogicAnalyzer::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                     Box::new(FakeMemory::new(BUFFER_SIZE as usize * 2))).unwrap()
+    }
+
+    fn fill_buffer(la: &LogicAnalyzer) {
+        for i in 0..BUFFER_SIZE as usize {
+            fake::write_u16(la.buffer_mmap(), i * 2, i as u16);
+        }
+    }
+
+    #[test]
+    fn mapping_size() {
+        let buffer = || Box::new(FakeMemory::new(BUFFER_SIZE as usize * 2));
+        assert!(LogicAnalyzer::from_mappings(Box::new(FakeMemory::new(4)), buffer()).is_err());
+        assert!(LogicAnalyzer::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                             Box::new(FakeMemory::new(0x100))).is_err());
+        assert!(LogicAnalyzer::from_mappings(Box::new(FakeMemory::new(0x100)), buffer()).is_ok());
+    }
+
+    #[test]
+    fn decimation() {
+        let mut la = logic_analyzer();
+        la.set_decimation(10);
+        assert_eq!(fake::read(la.reg_mmap(), 0x48), 9);
+        assert_eq!(la.decimation(), 10);
+        assert_eq!(la.sample_rate(), SAMPLING_FREQUENCY as f32 / 10.0);
+        la.set_decimation(0);
+        assert_eq!(la.decimation(), 1);
+        la.set_board(BoardInfo::with_family(BoardFamily::Sdrlab122_16));
+        la.set_decimation(2);
+        assert_eq!(la.sample_rate(), 61_440_000.0);
+    }
+
+    #[test]
+    fn trigger_setup() {
+        let mut la = logic_analyzer();
+        la.set_trigger_pre(100);
+        la.set_trigger_post(200);
+        la.set_trigger_mask(0x00ff);
+        la.set_trigger_value(0x0012);
+        la.set_pos_edge_trigger_mask(0x0100);
+        la.set_neg_edge_trigger_mask(0x0200);
+        la.set_trigger_source(TriggerSource::La | TriggerSource::Osc0);
+        let regs = la.reg_mmap();
+        assert_eq!(fake::read(regs, 0x10), 100);
+        assert_eq!(fake::read(regs, 0x14), 200);
+        assert_eq!(fake::read(regs, 0x20), 0x00ff);
+        assert_eq!(fake::read(regs, 0x24), 0x0012);
+        assert_eq!(fake::read(regs, 0x28), 0x0100);
+        assert_eq!(fake::read(regs, 0x2c), 0x0200);
+        assert_eq!(fake::read(regs, 0x08), 0b100100);
+        assert_eq!(la.trigger_source().iter().collect::<Vec<_>>(),
+                   vec![TriggerSource::Osc0, TriggerSource::La]);
+    }
+
+    #[test]
+    fn buffer_readout() {
+        let la = logic_analyzer();
+        fill_buffer(&la);
+        fake::write(la.reg_mmap(), 0x18, 100);
+        fake::write(la.reg_mmap(), 0x1c, 20);
+        assert_eq!(la.pointer(), 120);
+        assert_eq!(la.available(), 120);
+        assert_eq!(la.data(10), (110..120).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn buffer_readout_wrapped() {
+        let la = logic_analyzer();
+        fill_buffer(&la);
+        fake::write(la.reg_mmap(), 0x18, STATUS_OVERFLOW_MASK | 10);
+        assert_eq!(la.available(), BUFFER_SIZE as usize);
+        let data = la.data(20);
+        assert_eq!(&data[..10], &(BUFFER_SIZE as u16 - 10..BUFFER_SIZE as u16).collect::<Vec<_>>()[..]);
+        assert_eq!(&data[10..], &(0..10).collect::<Vec<_>>()[..]);
+    }
+
+    #[test]
+    fn data_clamped_to_available() {
+        let la = logic_analyzer();
+        fill_buffer(&la);
+        fake::write(la.reg_mmap(), 0x18, 3);
+        fake::write(la.reg_mmap(), 0x1c, 2);
+        assert_eq!(la.data(10), vec![0, 1, 2, 3, 4]);
+        assert_eq!(la.data(2), vec![3, 4]);
+    }
+
+    #[test]
+    fn trigger_position() {
+        let mut la = logic_analyzer();
+        fake::write(la.reg_mmap(), 0x18, 100);
+        assert_eq!(la.trigger_position(10), None);
+        la.trigger();
+        // Triggered, but the trigger sample wasn't stored yet.
+        assert_eq!(la.trigger_position(10), None);
+        fake::write(la.reg_mmap(), 0x1c, 1);
+        assert_eq!(la.trigger_position(10), Some(9));
+        fake::write(la.reg_mmap(), 0x1c, 5);
+        assert_eq!(la.trigger_position(10), Some(5));
+        assert_eq!(la.trigger_position(200), Some(100));
+        fake::write(la.reg_mmap(), 0x1c, 20);
+        assert_eq!(la.trigger_position(10), None);
+        fake::write(la.reg_mmap(), 0x18, STATUS_OVERFLOW_MASK | 10);
+        assert_eq!(la.trigger_position(BUFFER_SIZE as usize), Some(BUFFER_SIZE as usize - 20));
+        fake::write(la.reg_mmap(), 0x1c, STATUS_OVERFLOW_MASK);
+        assert_eq!(la.trigger_position(10), None);
+    }
+
+    #[test]
+    fn rle_words() {
+        let la = logic_analyzer();
+        for i in 0..RLE_BUFFER_SIZE as usize {
+            fake::write(la.buffer_mmap(), i * 4, i as u32);
+        }
+        // The sample counters don't matter in RLE mode.
+        fake::write(la.reg_mmap(), 0x18, 100);
+        fake::write(la.reg_mmap(), 0x38, 4);
+        assert_eq!(la.rle_words(), vec![0, 1, 2, 3]);
+        fake::write(la.reg_mmap(), 0x38, RLE_BUFFER_SIZE + 2);
+        let words = la.rle_words();
+        assert_eq!(words.len(), RLE_BUFFER_SIZE as usize);
+        assert_eq!(&words[..2], &[2, 3]);
+        assert_eq!(&words[words.len() - 2..], &[0, 1]);
+    }
+
+    #[test]
+    fn rle_data() {
+        let la = logic_analyzer();
+        fake::write(la.buffer_mmap(), 0, (2 << 16) | 5);
+        fake::write(la.buffer_mmap(), 4, 7);
+        fake::write(la.reg_mmap(), 0x38, 2);
+        assert_eq!(la.rle_data(), vec![Run { start: 0, value: 5, length: 3 },
+                                       Run { start: 3, value: 7, length: 1 }]);
+    }
+
+    #[test]
+    fn delay() {
+        let mut la = logic_analyzer();
+        la.set_decimation(5);
+        let samples = [0b00, 0b01, 0b01, 0b11, 0b11, 0b00];
+        let delay = la.delay(&samples, 0, Edge::Positive, 1, Edge::Positive).unwrap().unwrap();
+        assert!((delay.mean - 2.0 * la.sample_period()).abs() < 1e-12);
+        assert_eq!(la.measure(&samples, 1).unwrap().rising_edges, 1);
+    }
+
+    #[test]
+    fn capture_timeout() {
+        let mut la = logic_analyzer();
+        // Nothing clears the start bit of the fake registers.
+        match la.capture(Duration::from_millis(10)) {
+            Err(Error::Timeout(_)) => {}
+            _ => panic!("capture should time out"),
+        }
+        assert_eq!(fake::read(la.reg_mmap(), 0x00) & 0x4, 0x4);
+        assert!(la.wait_irq().is_err());
+    }
+}
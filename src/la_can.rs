@@ -0,0 +1,364 @@
+use std::cmp;
+use error;
+use la_channels::check_channel;
+use la_decode::{self, level, Decoder};
+
+/// CAN CRC-15 generator polynomial.
+const CRC15_POLY: u16 = 0x4599;
+/// Recessive bits required before a start of frame, the intermission.
+const INTERMISSION_BITS: f32 = 3.0;
+
+/// Error ending a frame early.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CanError {
+    /// Six equal bits in the stuffed part of the frame.
+    Stuff,
+    /// A fixed-form bit (delimiter or end of frame) was dominant.
+    Form,
+}
+
+/// Decoded classic CAN frame.
+///
+/// Fields after an error keep their default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanFrame {
+    /// Sample index of the start of frame bit.
+    pub start: usize,
+    /// Sample index of the last bit read.
+    pub end: usize,
+    /// Time of the start of frame bit in seconds.
+    pub time: f32,
+    /// 11-bit or 29-bit identifier.
+    pub id: u32,
+    pub extended: bool,
+    /// Remote transmission request.
+    pub rtr: bool,
+    /// Data length code.
+    pub dlc: u8,
+    pub data: Vec<u8>,
+    pub crc: u16,
+    /// The received CRC matches the one computed over the frame.
+    pub crc_ok: bool,
+    /// A receiver acknowledged the frame.
+    pub ack: bool,
+    pub error: Option<CanError>,
+}
+
+/// Reason for stopping to read a frame.
+enum Stop {
+    /// Capture ended in the middle of the frame.
+    End,
+    Error(CanError),
+}
+
+/// Samples bits, resynchronizing on recessive to dominant edges.
+struct BitReader<'a> {
+    samples: &'a [u16],
+    channel: usize,
+    bit: f32,
+    sample_point: f32,
+    /// Sample index of the last synchronization edge.
+    sync: usize,
+    /// Bits read since the last synchronization edge.
+    n: usize,
+    /// Sample index of the last sample point.
+    last: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn line(&self, i: usize) -> bool {
+        level(self.samples[i], self.channel)
+    }
+
+    fn raw(&mut self) -> Result<bool, Stop> {
+        let start = self.sync as f32 + self.n as f32 * self.bit;
+        let from = cmp::max(self.last + 1, (start - self.bit / 2.0).max(1.0) as usize);
+        let to = ((start + self.bit * self.sample_point) as usize).min(self.samples.len());
+        if let Some(edge) = (from..to).find(|i| self.line(i - 1) && !self.line(*i)) {
+            self.sync = edge;
+            self.n = 0;
+        }
+        let pos = (self.sync as f32 + (self.n as f32 + self.sample_point) * self.bit) as usize;
+        if pos >= self.samples.len() {
+            return Err(Stop::End);
+        }
+        self.last = pos;
+        self.n += 1;
+        Ok(self.line(pos))
+    }
+}
+
+/// Removes stuff bits and computes the CRC.
+struct Destuffer<'a> {
+    reader: BitReader<'a>,
+    last: bool,
+    run: u32,
+    crc: u16,
+}
+
+impl<'a> Destuffer<'a> {
+    /// Read the stuff bit following five equal bits, if any.
+    fn stuff(&mut self) -> Result<(), Stop> {
+        if self.run == 5 {
+            let stuff = self.reader.raw()?;
+            if stuff == self.last {
+                return Err(Stop::Error(CanError::Stuff));
+            }
+            self.last = stuff;
+            self.run = 1;
+        }
+        Ok(())
+    }
+
+    fn bit(&mut self) -> Result<bool, Stop> {
+        self.stuff()?;
+        let bit = self.reader.raw()?;
+        if bit == self.last {
+            self.run += 1;
+        } else {
+            self.last = bit;
+            self.run = 1;
+        }
+        let crc_next = bit ^ (self.crc >> 14 & 1 != 0);
+        self.crc = (self.crc << 1) & 0x7fff;
+        if crc_next {
+            self.crc ^= CRC15_POLY;
+        }
+        Ok(bit)
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, Stop> {
+        let mut value = 0;
+        for _ in 0..n {
+            value = value << 1 | self.bit()? as u32;
+        }
+        Ok(value)
+    }
+
+    /// Read a recessive fixed-form bit.
+    fn delimiter(&mut self) -> Result<(), Stop> {
+        if self.reader.raw()? {
+            Ok(())
+        } else {
+            Err(Stop::Error(CanError::Form))
+        }
+    }
+}
+
+/// Classic CAN decoder for a receive or bus logic analyzer channel.
+///
+/// The line is recessive high, as on the RX pin of a transceiver.
+pub struct Can {
+    channel: usize,
+    bitrate: u32,
+    sample_point: f32,
+}
+
+impl Can {
+    pub fn new(channel: usize, bitrate: u32) -> error::Result<Self> {
+        check_channel(channel)?;
+        Ok(Can { channel, bitrate, sample_point: 0.7 })
+    }
+
+    /// Set the sample point as fraction of the bit time.
+    pub fn set_sample_point(&mut self, sample_point: f32) {
+        self.sample_point = sample_point.clamp(0.1, 0.9);
+    }
+
+    /// Read the fields of a frame after the start of frame bit.
+    fn frame(&self, d: &mut Destuffer, frame: &mut CanFrame) -> Result<(), Stop> {
+        let id = d.bits(11)?;
+        let rtr_srr = d.bit()?;
+        frame.extended = d.bit()?;
+        if frame.extended {
+            frame.id = id << 18 | d.bits(18)?;
+            frame.rtr = d.bit()?;
+            d.bits(2)?;
+        } else {
+            frame.id = id;
+            frame.rtr = rtr_srr;
+            d.bit()?;
+        }
+        frame.dlc = d.bits(4)? as u8;
+        if !frame.rtr {
+            for _ in 0..frame.dlc.min(8) {
+                frame.data.push(d.bits(8)? as u8);
+            }
+        }
+        let crc = d.crc;
+        frame.crc = d.bits(15)? as u16;
+        frame.crc_ok = frame.crc == crc;
+        // Stuffing ends with the CRC, including a stuff bit after its last bit.
+        d.stuff()?;
+        d.delimiter()?;
+        frame.ack = !d.reader.raw()?;
+        d.delimiter()?;
+        for _ in 0..7 {
+            d.delimiter()?;
+        }
+        Ok(())
+    }
+}
+
+impl Decoder for Can {
+    type Output = Vec<CanFrame>;
+
+    /// Decode frames from a capture sampled at `sample_rate` Hz.
+    ///
+    /// Frames cut off by the end of the capture are dropped, frames with
+    /// errors are returned up to the erroneous bit.
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> Vec<CanFrame> {
+        let bit = sample_rate / self.bitrate as f32;
+        let intermission = (INTERMISSION_BITS * bit) as usize;
+        let mut frames = Vec::new();
+        let mut recessive = 0;
+        let mut i = 0;
+        while i < samples.len() {
+            if level(samples[i], self.channel) {
+                recessive += 1;
+                i += 1;
+                continue;
+            }
+            if recessive < intermission {
+                recessive = 0;
+                i += 1;
+                continue;
+            }
+            let reader = BitReader {
+                samples,
+                channel: self.channel,
+                bit,
+                sample_point: self.sample_point,
+                sync: i,
+                n: 1,
+                last: i,
+            };
+            let mut d = Destuffer { reader, last: false, run: 1, crc: 0 };
+            let mut frame = CanFrame {
+                start: i,
+                end: i,
+                time: la_decode::to_seconds(i, sample_rate),
+                id: 0,
+                extended: false,
+                rtr: false,
+                dlc: 0,
+                data: Vec::new(),
+                crc: 0,
+                crc_ok: false,
+                ack: false,
+                error: None,
+            };
+            match self.frame(&mut d, &mut frame) {
+                Err(Stop::End) => break,
+                Err(Stop::Error(err)) => frame.error = Some(err),
+                Ok(()) => {}
+            }
+            frame.end = d.reader.last;
+            frames.push(frame);
+            i = d.reader.last + 1;
+            recessive = 0;
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples per bit at 100 kbit/s and 1 MHz sample rate.
+    const SPB: usize = 10;
+
+    /// Bits of a standard data frame with `id` and `data` up to the CRC.
+    fn unstuffed(id: u32, data: &[u8]) -> Vec<bool> {
+        let mut bits = vec![false];
+        bits.extend((0..11).rev().map(|n| id >> n & 1 != 0));
+        // RTR, IDE and r0 are dominant.
+        bits.extend(&[false, false, false]);
+        bits.extend((0..4).rev().map(|n| data.len() >> n & 1 != 0));
+        for byte in data {
+            bits.extend((0..8).rev().map(|n| byte >> n & 1 != 0));
+        }
+        let crc = bits.iter().fold(0u16, |crc, &bit| {
+            let crc_next = bit ^ (crc >> 14 & 1 != 0);
+            let crc = (crc << 1) & 0x7fff;
+            if crc_next { crc ^ CRC15_POLY } else { crc }
+        });
+        bits.extend((0..15).rev().map(|n| crc >> n & 1 != 0));
+        bits
+    }
+
+    /// Insert a complementary bit after five equal bits.
+    fn stuff(bits: &[bool]) -> Vec<bool> {
+        let mut wire = Vec::new();
+        let mut run = 0;
+        for (i, &bit) in bits.iter().enumerate() {
+            run = if i > 0 && wire.last() == Some(&bit) { run + 1 } else { 1 };
+            wire.push(bit);
+            if run == 5 {
+                wire.push(!bit);
+                run = 1;
+            }
+        }
+        wire
+    }
+
+    /// Acknowledged frame on the wire.
+    fn frame_bits(bits: &[bool]) -> Vec<bool> {
+        let mut wire = stuff(bits);
+        // CRC delimiter, ACK slot, ACK delimiter and end of frame.
+        wire.extend(&[true, false, true]);
+        wire.extend(&[true; 7]);
+        wire
+    }
+
+    fn capture(frames: &[Vec<bool>]) -> Vec<u16> {
+        let mut samples = vec![1; 5 * SPB];
+        for bits in frames {
+            for &bit in bits {
+                samples.extend(vec![bit as u16; SPB]);
+            }
+            samples.extend(vec![1; 4 * SPB]);
+        }
+        samples
+    }
+
+    fn decode(samples: &[u16]) -> Vec<CanFrame> {
+        Can::new(0, 100_000).unwrap().decode(samples, 1e6)
+    }
+
+    #[test]
+    fn frames() {
+        let samples = capture(&[frame_bits(&unstuffed(0x123, &[0x00, 0xff, 0x55])),
+                                frame_bits(&unstuffed(0x7ff, &[]))]);
+        let frames = decode(&samples);
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].id, &frames[0].data[..], frames[0].crc_ok, frames[0].ack, frames[0].error),
+                   (0x123, &[0x00, 0xff, 0x55][..], true, true, None));
+        assert_eq!((frames[1].id, frames[1].dlc, frames[1].crc_ok, frames[1].error),
+                   (0x7ff, 0, true, None));
+    }
+
+    #[test]
+    fn stuff_bit_after_crc() {
+        // Find a frame whose CRC ends in a run of five equal bits.
+        let (id, bits) = (0..0x800)
+            .map(|id| (id, unstuffed(id, &[0x42])))
+            .find(|(_, bits)| stuff(bits).last() != bits.last())
+            .unwrap();
+        let frames = decode(&capture(&[frame_bits(&bits)]));
+        assert_eq!(frames.len(), 1);
+        assert_eq!((frames[0].id, frames[0].crc_ok, frames[0].ack, frames[0].error), (id, true, true, None));
+    }
+
+    #[test]
+    fn stuff_error() {
+        let mut bits = frame_bits(&unstuffed(0x123, &[0x00]));
+        // Six dominant bits including the start of frame.
+        for bit in &mut bits[1..6] {
+            *bit = false;
+        }
+        let frames = decode(&capture(&[bits]));
+        assert_eq!(frames[0].error, Some(CanError::Stuff));
+    }
+}
@@ -0,0 +1,81 @@
+use error::{Error, Result};
+use la::LogicAnalyzer;
+use la_mask::LaMaskRegsAPI;
+
+/// Number of logic analyzer channels.
+pub const CHANNELS: usize = 16;
+
+/// Fail with `Error::InvalidChannel` if `channel` is not a logic analyzer channel.
+pub fn check_channel(channel: usize) -> Result<()> {
+    if channel < CHANNELS {
+        Ok(())
+    } else {
+        Err(Error::InvalidChannel(channel))
+    }
+}
+
+/// Logic analyzer channels enabled in an input mask and their names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channels {
+    mask: u32,
+    names: Vec<String>,
+}
+
+impl Channels {
+    /// Channels in `mask`, named `<prefix><channel>` by default.
+    pub fn new(mask: u32, prefix: &str) -> Self {
+        Channels {
+            mask,
+            names: (0..CHANNELS).map(|ch| format!("{}{}", prefix, ch)).collect(),
+        }
+    }
+
+    /// Channels in the input mask of `la`.
+    pub fn from_la(la: &LogicAnalyzer, prefix: &str) -> Self {
+        Channels::new(la.input_mask(), prefix)
+    }
+
+    /// Input mask, one bit per channel.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Name of `channel`, `None` if it is out of range.
+    pub fn name(&self, channel: usize) -> Option<&str> {
+        self.names.get(channel).map(|name| &**name)
+    }
+
+    /// Set the name of `channel`.
+    pub fn set_name(&mut self, channel: usize, name: &str) -> Result<()> {
+        check_channel(channel)?;
+        self.names[channel] = name.to_owned();
+        Ok(())
+    }
+
+    /// Enabled channels in ascending order with their names.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        let mask = self.mask;
+        self.names.iter()
+            .enumerate()
+            .filter(move |&(ch, _)| mask & (1 << ch) != 0)
+            .map(|(ch, name)| (ch, &**name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let mut channels = Channels::new(0b1001, "D");
+        channels.set_name(3, "clk").unwrap();
+        assert_eq!(channels.iter().collect::<Vec<_>>(), vec![(0, "D0"), (3, "clk")]);
+        assert_eq!(channels.name(1), Some("D1"));
+        assert_eq!(channels.name(CHANNELS), None);
+        match channels.set_name(CHANNELS, "x") {
+            Err(Error::InvalidChannel(CHANNELS)) => {}
+            _ => panic!("channel out of range should be rejected"),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+/// Protocol decoder for ordered logic analyzer captures.
+///
+/// Decoders are configured with the channels and protocol parameters and
+/// can then be run over any number of captures.
+pub trait Decoder {
+    /// Decoded frames, events or transactions.
+    type Output;
+
+    /// Decode a capture sampled at `sample_rate` Hz.
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> Self::Output;
+}
+
+/// Level of `channel` in a logic analyzer sample.
+///
+/// Channels beyond the 16 of a sample read low, decoders reject them when
+/// configured, see `la_channels::check_channel`.
+pub fn level(sample: u16, channel: usize) -> bool {
+    channel < 16 && (sample >> channel) & 1 != 0
+}
+
+/// Levels of `channel` over a capture.
+pub fn levels<'a>(samples: &'a [u16], channel: usize) -> impl Iterator<Item = bool> + 'a {
+    samples.iter().map(move |sample| level(*sample, channel))
+}
+
+/// Sample indices at which `channel` changes level, with the new level.
+pub fn edges<'a>(samples: &'a [u16], channel: usize) -> impl Iterator<Item = (usize, bool)> + 'a {
+    samples.windows(2)
+        .enumerate()
+        .filter(move |&(_, pair)| level(pair[0], channel) != level(pair[1], channel))
+        .map(move |(i, pair)| (i + 1, level(pair[1], channel)))
+}
+
+/// Time of the sample at `index` in seconds from the start of the capture.
+pub fn to_seconds(index: usize, sample_rate: f32) -> f32 {
+    index as f32 / sample_rate
+}
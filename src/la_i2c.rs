@@ -0,0 +1,340 @@
+use error::Result;
+use la_channels::check_channel;
+use la_decode::{self, level, Decoder};
+
+/// Address prefix `11110` of the first byte of a 10-bit address.
+const TEN_BIT_PREFIX: u8 = 0b1111_0000;
+const TEN_BIT_MASK: u8 = 0b1111_1000;
+
+/// Violation of the I2C protocol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I2cBusError {
+    /// START or STOP before all bits of a byte were clocked.
+    IncompleteByte,
+    /// Clock pulses outside of a transfer.
+    MissingStart,
+}
+
+/// What happened on the bus.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I2cEventKind {
+    Start,
+    RepeatedStart,
+    Stop,
+    /// Address byte(s) following a START and whether they were acknowledged.
+    ///
+    /// A NACKed first byte of a 10-bit address is reported with the high
+    /// address bits only.
+    Address { address: u16, read: bool, ten_bit: bool, ack: bool },
+    /// Acknowledged first byte of a 10-bit address written, holding the
+    /// high address bits. The `Address` follows with the second byte.
+    TenBitPrefix { high: u16 },
+    /// Data byte and whether it was acknowledged.
+    Data { value: u8, ack: bool },
+    /// SCL was held low for the given number of samples.
+    ClockStretch(usize),
+    BusError(I2cBusError),
+}
+
+/// Decoded bus event.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct I2cEvent {
+    /// Sample index of the event, for bytes the first data bit.
+    pub index: usize,
+    /// Time of the event in seconds.
+    pub time: f32,
+    pub kind: I2cEventKind,
+}
+
+/// I2C decoder for the SCL and SDA logic analyzer channels.
+pub struct I2c {
+    scl: usize,
+    sda: usize,
+    stretch_factor: f32,
+}
+
+impl I2c {
+    pub fn new(scl: usize, sda: usize) -> Result<Self> {
+        check_channel(scl)?;
+        check_channel(sda)?;
+        Ok(I2c { scl, sda, stretch_factor: 2.0 })
+    }
+
+    /// Set how many times longer than the median SCL low phase a low phase
+    /// has to be to count as clock stretching.
+    pub fn set_stretch_factor(&mut self, factor: f32) {
+        self.stretch_factor = factor;
+    }
+
+    /// Find SCL low phases much longer than the median one.
+    fn clock_stretching(&self, samples: &[u16], sample_rate: f32) -> Vec<I2cEvent> {
+        let mut lows = Vec::new();
+        let mut fall = None;
+        for (i, rising) in la_decode::edges(samples, self.scl) {
+            match (rising, fall) {
+                (false, _) => fall = Some(i),
+                (true, Some(start)) => {
+                    lows.push((start, i - start));
+                    fall = None;
+                }
+                (true, None) => {}
+            }
+        }
+        if lows.is_empty() {
+            return Vec::new();
+        }
+        let mut lengths: Vec<usize> = lows.iter().map(|&(_, len)| len).collect();
+        lengths.sort();
+        let median = lengths[lengths.len() / 2] as f32;
+        lows.into_iter()
+            .filter(|&(_, len)| len as f32 > median * self.stretch_factor)
+            .map(|(index, len)| I2cEvent {
+                index,
+                time: la_decode::to_seconds(index, sample_rate),
+                kind: I2cEventKind::ClockStretch(len),
+            })
+            .collect()
+    }
+}
+
+impl Decoder for I2c {
+    type Output = Vec<I2cEvent>;
+
+    /// Decode events from a capture sampled at `sample_rate` Hz.
+    ///
+    /// Events are ordered by sample index.
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> Vec<I2cEvent> {
+        let mut events = Vec::new();
+        let mut push = |index: usize, kind: I2cEventKind| {
+            events.push(I2cEvent { index, time: la_decode::to_seconds(index, sample_rate), kind });
+        };
+
+        let mut active = false;
+        let mut missing_start = false;
+        let mut first_byte = true;
+        let mut ten_bit: Option<u16> = None;
+        let (mut byte, mut bits, mut byte_start) = (0u16, 0, 0);
+        for i in 1..samples.len() {
+            let (scl0, sda0) = (level(samples[i - 1], self.scl), level(samples[i - 1], self.sda));
+            let (scl, sda) = (level(samples[i], self.scl), level(samples[i], self.sda));
+            if scl0 && scl && sda0 != sda {
+                // SDA changing while SCL is high is a START or STOP, the
+                // preceding rising SCL edge was sampled as one bit.
+                if bits > 1 {
+                    push(i, I2cEventKind::BusError(I2cBusError::IncompleteByte));
+                }
+                if !sda {
+                    push(i, if active { I2cEventKind::RepeatedStart } else { I2cEventKind::Start });
+                    active = true;
+                    first_byte = true;
+                } else {
+                    push(i, I2cEventKind::Stop);
+                    active = false;
+                    ten_bit = None;
+                }
+                missing_start = false;
+                bits = 0;
+                byte = 0;
+                continue;
+            }
+            if scl0 || !scl {
+                continue;
+            }
+            // Rising SCL edge samples a bit.
+            if !active {
+                if !missing_start {
+                    push(i, I2cEventKind::BusError(I2cBusError::MissingStart));
+                    missing_start = true;
+                }
+                continue;
+            }
+            if bits == 0 {
+                byte_start = i;
+            }
+            byte = byte << 1 | sda as u16;
+            bits += 1;
+            if bits < 9 {
+                continue;
+            }
+            let value = (byte >> 1) as u8;
+            let ack = byte & 1 == 0;
+            bits = 0;
+            byte = 0;
+            if first_byte {
+                first_byte = false;
+                let read = value & 1 != 0;
+                if value & TEN_BIT_MASK == TEN_BIT_PREFIX {
+                    let high = ((value >> 1) & 0b11) as u16;
+                    match (read, ten_bit) {
+                        // A read repeats the high bits after a repeated START.
+                        (true, Some(address)) if address >> 8 == high =>
+                            push(byte_start, I2cEventKind::Address { address, read, ten_bit: true, ack }),
+                        (true, _) =>
+                            push(byte_start, I2cEventKind::Address { address: high << 8, read, ten_bit: true, ack }),
+                        (false, _) if ack => {
+                            push(byte_start, I2cEventKind::TenBitPrefix { high });
+                            ten_bit = Some(high << 8 | 0x8000);
+                        }
+                        // No device answered, the second byte is data if any.
+                        (false, _) => {
+                            push(byte_start, I2cEventKind::Address { address: high << 8, read, ten_bit: true, ack });
+                            ten_bit = None;
+                        }
+                    }
+                } else {
+                    push(byte_start, I2cEventKind::Address { address: (value >> 1) as u16, read, ten_bit: false, ack });
+                }
+                continue;
+            }
+            match ten_bit {
+                // Second byte of a 10-bit address, marked by bit 15.
+                Some(high) if high & 0x8000 != 0 => {
+                    let address = (high & 0x300) | value as u16;
+                    ten_bit = Some(address);
+                    push(byte_start, I2cEventKind::Address { address, read: false, ten_bit: true, ack });
+                }
+                _ => push(byte_start, I2cEventKind::Data { value, ack }),
+            }
+        }
+
+        events.extend(self.clock_stretching(samples, sample_rate));
+        events.sort_by_key(|event| event.index);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::I2cEventKind::*;
+
+    /// Capture builder with SCL on channel 0 and SDA on channel 1.
+    struct Bus(Vec<u16>);
+
+    impl Bus {
+        fn new() -> Self {
+            let mut bus = Bus(Vec::new());
+            bus.level(1, 1, 10);
+            bus
+        }
+
+        fn level(&mut self, scl: u16, sda: u16, samples: usize) {
+            self.0.extend(vec![scl | sda << 1; samples]);
+        }
+
+        fn start(&mut self) {
+            self.level(1, 1, 4);
+            self.level(1, 0, 4);
+            self.level(0, 0, 4);
+        }
+
+        fn repeated_start(&mut self) {
+            self.level(0, 1, 4);
+            self.start();
+        }
+
+        fn stop(&mut self) {
+            self.level(0, 0, 4);
+            self.level(1, 0, 4);
+            self.level(1, 1, 4);
+        }
+
+        /// Clock out one bit, holding SCL low for `low` samples first.
+        fn bit(&mut self, bit: u16, low: usize) {
+            self.level(0, bit, low);
+            self.level(1, bit, 4);
+            self.level(0, bit, 1);
+        }
+
+        fn byte(&mut self, value: u8, ack: bool) {
+            for n in (0..8).rev() {
+                self.bit((value >> n) as u16 & 1, 4);
+            }
+            self.bit(!ack as u16, 4);
+        }
+
+        fn decode(&self) -> Vec<I2cEventKind> {
+            let events = I2c::new(0, 1).unwrap().decode(&self.0, 1e6);
+            assert!(events.windows(2).all(|pair| pair[0].index <= pair[1].index));
+            events.iter().map(|event| event.kind).collect()
+        }
+    }
+
+    #[test]
+    fn seven_bit() {
+        let mut bus = Bus::new();
+        bus.start();
+        bus.byte(0x48 << 1, true);
+        bus.byte(0x12, true);
+        bus.repeated_start();
+        bus.byte(0x48 << 1 | 1, true);
+        // The device stretches the clock before the first data bit.
+        for n in (0..8).rev() {
+            bus.bit((0x9a >> n) & 1, if n == 7 { 40 } else { 4 });
+        }
+        bus.bit(1, 4);
+        bus.stop();
+        bus.start();
+        bus.byte(0x10, true);
+        bus.bit(1, 4);
+        bus.stop();
+        assert_eq!(bus.decode(), vec![
+            Start,
+            Address { address: 0x48, read: false, ten_bit: false, ack: true },
+            Data { value: 0x12, ack: true },
+            RepeatedStart,
+            Address { address: 0x48, read: true, ten_bit: false, ack: true },
+            ClockStretch(41),
+            Data { value: 0x9a, ack: false },
+            Stop,
+            Start,
+            Address { address: 0x08, read: false, ten_bit: false, ack: true },
+            BusError(I2cBusError::IncompleteByte),
+            Stop,
+        ]);
+    }
+
+    #[test]
+    fn ten_bit() {
+        let mut bus = Bus::new();
+        bus.start();
+        bus.byte(0xf2, true);
+        bus.byte(0x34, false);
+        bus.stop();
+        bus.start();
+        bus.byte(0xf2, true);
+        bus.byte(0x34, true);
+        bus.repeated_start();
+        bus.byte(0xf3, true);
+        bus.byte(0x56, false);
+        bus.stop();
+        assert_eq!(bus.decode(), vec![
+            Start,
+            TenBitPrefix { high: 1 },
+            Address { address: 0x134, read: false, ten_bit: true, ack: false },
+            Stop,
+            Start,
+            TenBitPrefix { high: 1 },
+            Address { address: 0x134, read: false, ten_bit: true, ack: true },
+            RepeatedStart,
+            Address { address: 0x134, read: true, ten_bit: true, ack: true },
+            Data { value: 0x56, ack: false },
+            Stop,
+        ]);
+    }
+
+    #[test]
+    fn ten_bit_nack() {
+        let mut bus = Bus::new();
+        bus.start();
+        bus.byte(0xf6, false);
+        bus.byte(0x34, false);
+        bus.stop();
+        assert_eq!(bus.decode(), vec![
+            Start,
+            Address { address: 0x300, read: false, ten_bit: true, ack: false },
+            Data { value: 0x34, ack: false },
+            Stop,
+        ]);
+    }
+}
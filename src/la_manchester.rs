@@ -0,0 +1,160 @@
+use error::Result;
+use la_channels::check_channel;
+use la_decode::{self, Decoder};
+
+/// Line code of a self-clocking signal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    /// Manchester, a rising edge in the middle of the bit is a 1 (IEEE 802.3).
+    ManchesterIeee,
+    /// Manchester, a falling edge in the middle of the bit is a 1 (G.E. Thomas).
+    ManchesterThomas,
+    /// Biphase mark, a transition in the middle of the bit is a 1.
+    BiphaseMark,
+    /// Biphase space, a transition in the middle of the bit is a 0.
+    BiphaseSpace,
+}
+
+/// Decoded bit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ManchesterBit {
+    /// Sample index of the transition the bit was decoded from.
+    pub index: usize,
+    /// Time of the transition in seconds.
+    pub time: f32,
+    pub value: bool,
+}
+
+/// Manchester and biphase decoder.
+///
+/// Synchronizes on the first interval of a full bit period, which always
+/// ends in the middle (Manchester) or starts at the boundary (biphase) of
+/// a bit. Missing transitions lose synchronization until the next such
+/// interval, so the output can have gaps.
+pub struct Manchester {
+    channel: usize,
+    bitrate: u32,
+    encoding: Encoding,
+}
+
+impl Manchester {
+    pub fn new(channel: usize, bitrate: u32, encoding: Encoding) -> Result<Self> {
+        check_channel(channel)?;
+        Ok(Manchester { channel, bitrate, encoding })
+    }
+}
+
+impl Decoder for Manchester {
+    type Output = Vec<ManchesterBit>;
+
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> Vec<ManchesterBit> {
+        let period = sample_rate / self.bitrate as f32;
+        let edges: Vec<(usize, bool)> = la_decode::edges(samples, self.channel).collect();
+        // Index of the edge closest to `pos`, within a quarter bit period.
+        let near = |from: usize, pos: f32| {
+            edges[from..].iter()
+                .take_while(|&&(i, _)| i as f32 <= pos + period / 4.0)
+                .position(|&(i, _)| (i as f32 - pos).abs() <= period / 4.0)
+                .map(|n| from + n)
+        };
+        let long = |n: usize| {
+            let interval = (edges[n + 1].0 - edges[n].0) as f32;
+            (interval - period).abs() <= period / 4.0
+        };
+        let manchester = self.encoding == Encoding::ManchesterIeee ||
+            self.encoding == Encoding::ManchesterThomas;
+
+        let mut bits = Vec::new();
+        let mut push = |index: usize, value: bool| {
+            bits.push(ManchesterBit { index, time: la_decode::to_seconds(index, sample_rate), value });
+        };
+        let mut n = 0;
+        while n + 1 < edges.len() {
+            if !long(n) {
+                n += 1;
+                continue;
+            }
+            // Manchester: edge n + 1 is in the middle of a bit.
+            // Biphase: edge n is at the start of a bit.
+            let mut current = if manchester { n + 1 } else { n };
+            loop {
+                let (index, rising) = edges[current];
+                let next = near(current + 1, index as f32 + period);
+                if manchester {
+                    push(index, rising == (self.encoding == Encoding::ManchesterIeee));
+                    match next {
+                        Some(next) => current = next,
+                        None => break,
+                    }
+                } else {
+                    let next = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let mid = near(current + 1, index as f32 + period / 2.0)
+                        .filter(|mid| *mid < next);
+                    push(index, mid.is_some() == (self.encoding == Encoding::BiphaseMark));
+                    current = next;
+                }
+            }
+            n = current + 1;
+        }
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples per half bit at 100 kbit/s and 1 MHz sample rate.
+    const HALF: usize = 5;
+    const DATA: [bool; 10] = [false, true, true, false, false, false, true, false, true, true];
+
+    /// Encode `bits`, starting with the line low.
+    fn encode(encoding: Encoding, bits: &[bool]) -> Vec<u16> {
+        let mut samples = Vec::new();
+        let mut level = 0;
+        for &bit in bits {
+            let (first, second) = match encoding {
+                Encoding::ManchesterIeee => (!bit as u16, bit as u16),
+                Encoding::ManchesterThomas => (bit as u16, !bit as u16),
+                Encoding::BiphaseMark | Encoding::BiphaseSpace => {
+                    let mid = bit == (encoding == Encoding::BiphaseMark);
+                    let first = level ^ 1;
+                    (first, first ^ mid as u16)
+                }
+            };
+            samples.extend(vec![first; HALF]);
+            samples.extend(vec![second; HALF]);
+            level = second;
+        }
+        // Biphase bits are decoded up to the transition starting the next bit.
+        samples.push(level ^ 1);
+        samples
+    }
+
+    fn decode(encoding: Encoding, samples: &[u16]) -> Vec<bool> {
+        Manchester::new(0, 100_000, encoding).unwrap()
+            .decode(samples, 1e6)
+            .iter()
+            .map(|bit| bit.value)
+            .collect()
+    }
+
+    #[test]
+    fn encodings() {
+        // Bits before the first interval of a full bit period are lost.
+        // Manchester synchronizes on the 0 -> 1 change after the first bit,
+        // biphase on the first bit without a transition in its middle.
+        let first_bit = [
+            (Encoding::ManchesterIeee, 1),
+            (Encoding::ManchesterThomas, 1),
+            (Encoding::BiphaseMark, 3),
+            (Encoding::BiphaseSpace, 1),
+        ];
+        for &(encoding, first) in &first_bit {
+            assert_eq!(decode(encoding, &encode(encoding, &DATA)), &DATA[first..], "{:?}", encoding);
+        }
+    }
+}
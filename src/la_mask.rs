@@ -1,3 +1,4 @@
+use std::cmp;
 use uio::*;
 use volatile_register::RW;
 
@@ -41,6 +42,8 @@ pub trait LaMaskRegsAPI {
     /// Get decimation factor.
     fn decimation(&self) -> u32;
     /// Set decimation factor.
+    ///
+    /// A factor of 0 is treated as 1, no decimation.
     fn set_decimation(&mut self, dec: u32);
 }
 
@@ -61,6 +64,6 @@ impl LaMaskRegsAPI for LaMaskRegs {
         self.cfg_dec.read() + 1
     }
     fn set_decimation(&mut self, dec: u32) {
-        unsafe { self.cfg_dec.write(dec - 1); }
+        unsafe { self.cfg_dec.write(cmp::max(dec, 1) - 1); }
     }
 }
@@ -0,0 +1,194 @@
+use error::Result;
+use la_channels::check_channel;
+use la_decode;
+use osc_trigger::Edge;
+
+/// Minimum, maximum and mean of a set of durations in seconds.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// Standard deviation.
+    pub std_dev: f32,
+    /// Number of durations.
+    pub count: usize,
+}
+
+impl Stats {
+    /// Statistics of `values`, `None` if there are none.
+    pub fn new(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let count = values.len();
+        let mean = values.iter().sum::<f32>() / count as f32;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / count as f32;
+        Some(Stats {
+            min: values.iter().cloned().fold(f32::INFINITY, f32::min),
+            max: values.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+            mean,
+            std_dev: variance.sqrt(),
+            count,
+        })
+    }
+}
+
+/// Measurements of one logic analyzer channel.
+///
+/// Only complete periods and pulses between two edges are measured.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Measurements {
+    pub rising_edges: usize,
+    pub falling_edges: usize,
+    /// Time between rising edges in seconds.
+    pub period: Option<Stats>,
+    /// Frequency in Hz from the mean period.
+    pub frequency: Option<f32>,
+    /// Period jitter (standard deviation of the period) in seconds.
+    pub jitter: Option<f32>,
+    /// Fraction of the time between the first and last rising edge spent high.
+    pub duty_cycle: Option<f32>,
+    /// High pulse widths in seconds.
+    pub high: Option<Stats>,
+    /// Low pulse widths in seconds.
+    pub low: Option<Stats>,
+}
+
+/// Measure `channel` of a capture sampled every `sample_period` seconds.
+pub fn measure(samples: &[u16], channel: usize, sample_period: f32) -> Result<Measurements> {
+    check_channel(channel)?;
+    let edges: Vec<(usize, bool)> = la_decode::edges(samples, channel).collect();
+    let seconds = |samples: usize| samples as f32 * sample_period;
+    let rising: Vec<usize> = edges.iter().filter(|e| e.1).map(|e| e.0).collect();
+
+    let periods: Vec<f32> = rising.windows(2).map(|pair| seconds(pair[1] - pair[0])).collect();
+    let (mut high, mut low) = (Vec::new(), Vec::new());
+    for pair in edges.windows(2) {
+        let width = seconds(pair[1].0 - pair[0].0);
+        if pair[0].1 {
+            high.push(width);
+        } else {
+            low.push(width);
+        }
+    }
+    let duty_cycle = match (rising.first(), rising.last()) {
+        (Some(&first), Some(&last)) if last > first => {
+            let high_time: usize = edges.windows(2)
+                .filter(|pair| pair[0].1 && pair[0].0 >= first && pair[1].0 <= last)
+                .map(|pair| pair[1].0 - pair[0].0)
+                .sum();
+            Some(high_time as f32 / (last - first) as f32)
+        }
+        _ => None,
+    };
+    let period = Stats::new(&periods);
+
+    Ok(Measurements {
+        rising_edges: rising.len(),
+        falling_edges: edges.len() - rising.len(),
+        period,
+        frequency: period.map(|period| 1.0 / period.mean),
+        jitter: period.map(|period| period.std_dev),
+        duty_cycle,
+        high: Stats::new(&high),
+        low: Stats::new(&low),
+    })
+}
+
+/// Delay from each `from_edge` on channel `from` to the next `to_edge` on
+/// channel `to`, before the following `from_edge`.
+///
+/// `to` edges coinciding with the `from` edge count as zero delay. Returns
+/// `None` if no delay was measured.
+pub fn delay(samples: &[u16], from: usize, from_edge: Edge, to: usize, to_edge: Edge,
+             sample_period: f32) -> Result<Option<Stats>> {
+    check_channel(from)?;
+    check_channel(to)?;
+    let select = |channel: usize, edge: Edge| -> Vec<usize> {
+        la_decode::edges(samples, channel)
+            .filter(|&(_, rising)| rising == (edge == Edge::Positive))
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let starts = select(from, from_edge);
+    let ends = select(to, to_edge);
+    let delays: Vec<f32> = starts.iter()
+        .enumerate()
+        .filter_map(|(n, &start)| {
+            let limit = starts.get(n + 1).cloned().unwrap_or(usize::MAX);
+            ends.iter()
+                .find(|&&end| end >= start && end < limit)
+                .map(|end| (end - start) as f32 * sample_period)
+        })
+        .collect();
+    Ok(Stats::new(&delays))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock on channel 0 with a period of 10 samples, high for 3, and the
+    /// same clock delayed by 2 samples on channel 1.
+    fn clock() -> Vec<u16> {
+        let clk = |i: usize| (i % 10 >= 5 && i % 10 < 8) as u16;
+        (0..100).map(|i| clk(i) | if i >= 2 { clk(i - 2) << 1 } else { 0 }).collect()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn edges_and_periods() {
+        let m = measure(&clock(), 0, 1e-6).unwrap();
+        assert_eq!((m.rising_edges, m.falling_edges), (10, 10));
+        let period = m.period.unwrap();
+        assert_eq!(period.count, 9);
+        assert!(close(period.mean, 10e-6) && close(period.min, 10e-6) && close(period.max, 10e-6));
+        assert!((m.frequency.unwrap() - 100e3).abs() < 1.0);
+        assert!(m.jitter.unwrap() < 1e-9);
+    }
+
+    #[test]
+    fn duty_cycle_and_pulses() {
+        let m = measure(&clock(), 0, 1e-6).unwrap();
+        assert!((m.duty_cycle.unwrap() - 0.3).abs() < 1e-6);
+        let (high, low) = (m.high.unwrap(), m.low.unwrap());
+        assert_eq!((high.count, low.count), (10, 9));
+        assert!(close(high.mean, 3e-6) && close(low.mean, 7e-6));
+        assert!(close(high.std_dev, 0.0));
+    }
+
+    #[test]
+    fn idle_channel() {
+        let m = measure(&clock(), 5, 1e-6).unwrap();
+        assert_eq!((m.rising_edges, m.falling_edges), (0, 0));
+        assert_eq!((m.period, m.frequency, m.duty_cycle, m.high, m.low), (None, None, None, None, None));
+        // A single pulse has widths but no period.
+        let m = measure(&[0, 1, 1, 0], 0, 1e-6).unwrap();
+        assert!(close(m.high.unwrap().mean, 2e-6));
+        assert_eq!((m.period, m.duty_cycle), (None, None));
+    }
+
+    #[test]
+    fn delays() {
+        let samples = clock();
+        let d = delay(&samples, 0, Edge::Positive, 1, Edge::Positive, 1e-6).unwrap().unwrap();
+        assert_eq!(d.count, 10);
+        assert!(close(d.mean, 2e-6) && close(d.std_dev, 0.0));
+        let d = delay(&samples, 0, Edge::Positive, 1, Edge::Negative, 1e-6).unwrap().unwrap();
+        assert!(close(d.mean, 5e-6));
+        // Coinciding edges count as zero delay.
+        let d = delay(&samples, 0, Edge::Positive, 0, Edge::Positive, 1e-6).unwrap().unwrap();
+        assert!(close(d.max, 0.0));
+        assert_eq!(delay(&samples, 0, Edge::Positive, 5, Edge::Positive, 1e-6).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_channel() {
+        assert!(measure(&clock(), 16, 1e-6).is_err());
+        assert!(delay(&clock(), 0, Edge::Positive, 16, Edge::Positive, 1e-6).is_err());
+    }
+}
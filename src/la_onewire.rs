@@ -0,0 +1,284 @@
+use error::Result;
+use la_channels::check_channel;
+use la_decode::{self, Decoder};
+
+/// Shortest low pulse accepted as reset, 480 us nominal.
+const RESET_MIN: f32 = 400e-6;
+/// Longest delay from the end of the reset to the presence pulse.
+const PRESENCE_DELAY_MAX: f32 = 100e-6;
+/// Presence pulse length range, 60 us to 240 us nominal.
+const PRESENCE_MIN: f32 = 40e-6;
+const PRESENCE_MAX: f32 = 300e-6;
+/// Low pulses shorter than this are 1 bits, longer ones 0 bits.
+const BIT_THRESHOLD: f32 = 15e-6;
+
+/// ROM command following a reset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RomCommand {
+    ReadRom,
+    MatchRom,
+    SearchRom,
+    AlarmSearch,
+    SkipRom,
+    Resume,
+    OverdriveSkipRom,
+    OverdriveMatchRom,
+    Unknown(u8),
+}
+
+impl From<u8> for RomCommand {
+    fn from(value: u8) -> Self {
+        match value {
+            0x33 => RomCommand::ReadRom,
+            0x55 => RomCommand::MatchRom,
+            0xf0 => RomCommand::SearchRom,
+            0xec => RomCommand::AlarmSearch,
+            0xcc => RomCommand::SkipRom,
+            0xa5 => RomCommand::Resume,
+            0x3c => RomCommand::OverdriveSkipRom,
+            0x69 => RomCommand::OverdriveMatchRom,
+            value => RomCommand::Unknown(value),
+        }
+    }
+}
+
+/// What happened on the bus.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OneWireEventKind {
+    /// Reset pulse and whether a device answered with a presence pulse.
+    Reset { presence: bool },
+    RomCommand(RomCommand),
+    /// 64-bit ROM id read or matched, family code in the low byte.
+    RomId { id: u64, crc_ok: bool },
+    /// Function command or data byte.
+    Byte(u8),
+}
+
+/// Decoded bus event.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OneWireEvent {
+    /// Sample index of the reset pulse or of the first bit.
+    pub index: usize,
+    /// Time of the event in seconds.
+    pub time: f32,
+    pub kind: OneWireEventKind,
+}
+
+/// Expected meaning of the next byte.
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    /// Nothing sensible before the first reset.
+    Idle,
+    RomCommand,
+    RomId(usize),
+    /// Search bits can't be decoded as bytes, skipped until the next reset.
+    Search,
+    Function,
+}
+
+/// 1-Wire decoder for standard speed.
+///
+/// Read and write slots are both decoded from the length of the low pulse,
+/// so bytes sent by the master and by devices look the same.
+pub struct OneWire {
+    channel: usize,
+}
+
+impl OneWire {
+    pub fn new(channel: usize) -> Result<Self> {
+        check_channel(channel)?;
+        Ok(OneWire { channel })
+    }
+}
+
+impl Decoder for OneWire {
+    type Output = Vec<OneWireEvent>;
+
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> Vec<OneWireEvent> {
+        let seconds = |samples: usize| samples as f32 / sample_rate;
+        let mut pulses = Vec::new();
+        let mut fall = None;
+        for (i, rising) in la_decode::edges(samples, self.channel) {
+            match (rising, fall) {
+                (false, _) => fall = Some(i),
+                (true, Some(start)) => {
+                    pulses.push((start, i));
+                    fall = None;
+                }
+                (true, None) => {}
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut push = |index: usize, kind: OneWireEventKind| {
+            events.push(OneWireEvent { index, time: la_decode::to_seconds(index, sample_rate), kind });
+        };
+        let mut state = State::Idle;
+        let (mut byte, mut bits, mut byte_start) = (0u8, 0, 0);
+        let mut rom = [0u8; 8];
+        let mut rom_start = 0;
+        let mut pulses = pulses.into_iter().peekable();
+        while let Some((start, end)) = pulses.next() {
+            if seconds(end - start) >= RESET_MIN {
+                let presence = match pulses.peek() {
+                    Some(&(s, e)) => seconds(s - end) <= PRESENCE_DELAY_MAX &&
+                        seconds(e - s) >= PRESENCE_MIN && seconds(e - s) <= PRESENCE_MAX,
+                    None => false,
+                };
+                if presence {
+                    pulses.next();
+                }
+                push(start, OneWireEventKind::Reset { presence });
+                state = State::RomCommand;
+                bits = 0;
+                continue;
+            }
+            if state == State::Idle || state == State::Search {
+                continue;
+            }
+            if bits == 0 {
+                byte_start = start;
+                byte = 0;
+            }
+            if seconds(end - start) < BIT_THRESHOLD {
+                byte |= 1 << bits;
+            }
+            bits += 1;
+            if bits < 8 {
+                continue;
+            }
+            bits = 0;
+            state = match state {
+                State::RomCommand => {
+                    let command = RomCommand::from(byte);
+                    push(byte_start, OneWireEventKind::RomCommand(command));
+                    match command {
+                        RomCommand::ReadRom | RomCommand::MatchRom | RomCommand::OverdriveMatchRom =>
+                            State::RomId(0),
+                        RomCommand::SearchRom | RomCommand::AlarmSearch => State::Search,
+                        _ => State::Function,
+                    }
+                }
+                State::RomId(n) => {
+                    if n == 0 {
+                        rom_start = byte_start;
+                    }
+                    rom[n] = byte;
+                    if n < 7 {
+                        State::RomId(n + 1)
+                    } else {
+                        let id = rom.iter().rev().fold(0u64, |id, byte| id << 8 | *byte as u64);
+                        let crc_ok = crc8(&rom[..7]) == rom[7];
+                        push(rom_start, OneWireEventKind::RomId { id, crc_ok });
+                        State::Function
+                    }
+                }
+                _ => {
+                    push(byte_start, OneWireEventKind::Byte(byte));
+                    State::Function
+                }
+            };
+        }
+        events
+    }
+}
+
+/// Dallas/Maxim CRC-8 of the ROM id.
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 1 != 0 { (crc >> 1) ^ 0x8c } else { crc >> 1 }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Capture sampled at 1 MHz, one sample per microsecond.
+    struct Bus(Vec<u16>);
+
+    impl Bus {
+        fn new() -> Self {
+            Bus(vec![1; 100])
+        }
+
+        fn pulse(&mut self, low: usize, high: usize) {
+            self.0.extend(vec![0; low]);
+            self.0.extend(vec![1; high]);
+        }
+
+        fn reset(&mut self, presence: bool) {
+            if presence {
+                self.pulse(500, 30);
+                self.pulse(120, 300);
+            } else {
+                self.pulse(500, 450);
+            }
+        }
+
+        /// Write or read slots of `byte`, LSB first.
+        fn byte(&mut self, byte: u8) {
+            for n in 0..8 {
+                let low = if byte >> n & 1 != 0 { 5 } else { 65 };
+                self.pulse(low, 70 - low);
+            }
+        }
+
+        fn decode(&self) -> Vec<OneWireEventKind> {
+            OneWire::new(0).unwrap().decode(&self.0, 1e6).iter().map(|event| event.kind).collect()
+        }
+    }
+
+    #[test]
+    fn match_rom() {
+        let mut rom = [0x28, 1, 2, 3, 4, 5, 6, 0];
+        rom[7] = crc8(&rom[..7]);
+        let mut bus = Bus::new();
+        bus.reset(true);
+        bus.byte(0x55);
+        for byte in &rom {
+            bus.byte(*byte);
+        }
+        bus.byte(0x44);
+        bus.reset(false);
+        assert_eq!(bus.decode(), vec![
+            OneWireEventKind::Reset { presence: true },
+            OneWireEventKind::RomCommand(RomCommand::MatchRom),
+            OneWireEventKind::RomId { id: u64::from_le_bytes(rom), crc_ok: true },
+            OneWireEventKind::Byte(0x44),
+            OneWireEventKind::Reset { presence: false },
+        ]);
+    }
+
+    #[test]
+    fn rom_crc_error() {
+        let mut bus = Bus::new();
+        bus.reset(true);
+        bus.byte(0x33);
+        for byte in &[0x28, 1, 2, 3, 4, 5, 6, 0] {
+            bus.byte(*byte);
+        }
+        assert_eq!(bus.decode()[2], OneWireEventKind::RomId { id: 0x0006_0504_0302_0128, crc_ok: false });
+    }
+
+    #[test]
+    fn search_skipped() {
+        let mut bus = Bus::new();
+        bus.byte(0xcc);
+        bus.reset(true);
+        bus.byte(0xf0);
+        bus.byte(0x12);
+        bus.reset(true);
+        bus.byte(0xcc);
+        bus.byte(0xbe);
+        assert_eq!(bus.decode(), vec![
+            OneWireEventKind::Reset { presence: true },
+            OneWireEventKind::RomCommand(RomCommand::SearchRom),
+            OneWireEventKind::Reset { presence: true },
+            OneWireEventKind::RomCommand(RomCommand::SkipRom),
+            OneWireEventKind::Byte(0xbe),
+        ]);
+    }
+}
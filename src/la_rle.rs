@@ -1,6 +1,12 @@
+use std::iter;
 use uio::*;
 use volatile_register::{RO, RW};
 
+/// Number of bits holding the sample value in an RLE word.
+pub const RLE_VALUE_BITS: u32 = 16;
+/// Number of bits holding the run length counter in an RLE word.
+pub const RLE_COUNTER_BITS: u32 = 16;
+
 #[repr(C)]
 pub struct LaRleRegs {
     /// RLE mode
@@ -33,6 +39,8 @@ pub trait LaRleRegsAPI {
     /// Current data stream length counter.
     fn counter_current(&self) -> u32;
     /// Last data stream length counter.
+    ///
+    /// In RLE mode this is the number of RLE words of the last stream.
     fn counter_last(&self) -> u32;
 }
 
@@ -50,3 +58,97 @@ impl LaRleRegsAPI for LaRleRegs {
         self.status_last.read()
     }
 }
+
+/// A run of identical samples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// Sample index of the first sample of the run.
+    pub start: u64,
+    /// Sample value.
+    pub value: u16,
+    /// Number of samples in the run.
+    pub length: u64,
+}
+
+/// Decode RLE words into runs.
+///
+/// Each word holds `{counter, value}` where the counter is the run length
+/// minus one. Runs longer than the counter field are split by the hardware
+/// into several words with the same value, these are merged again.
+pub fn decode(words: &[u32]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut start = 0;
+    for word in words {
+        let value = (word & ((1 << RLE_VALUE_BITS) - 1)) as u16;
+        let length = (word >> RLE_VALUE_BITS) as u64 + 1;
+        match runs.last_mut() {
+            Some(ref mut run) if run.value == value => run.length += length,
+            _ => runs.push(Run { start, value, length }),
+        }
+        start += length;
+    }
+    runs
+}
+
+/// Expand runs into samples with absolute timestamps.
+///
+/// Yields `(sample index, value)` pairs, multiply the sample index with
+/// the sample period to get the time.
+pub fn expand<'a>(runs: &'a [Run]) -> impl Iterator<Item = (u64, u16)> + 'a {
+    runs.iter().flat_map(|run| {
+        (run.start..run.start + run.length).zip(iter::repeat(run.value))
+    })
+}
+
+/// Compress plain samples into runs.
+pub fn from_samples(samples: &[u16]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for (i, value) in samples.iter().enumerate() {
+        match runs.last_mut() {
+            Some(ref mut run) if run.value == *value => run.length += 1,
+            _ => runs.push(Run { start: i as u64, value: *value, length: 1 }),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_runs() {
+        let runs = decode(&[(2 << 16) | 5, 7, (1 << 16) | 9]);
+        assert_eq!(runs, vec![Run { start: 0, value: 5, length: 3 },
+                              Run { start: 3, value: 7, length: 1 },
+                              Run { start: 4, value: 9, length: 2 }]);
+    }
+
+    #[test]
+    fn decode_counter_overflow() {
+        // A run of 65538 samples split into a full counter and a remainder.
+        let runs = decode(&[5, (0xffff << 16) | 7, (1 << 16) | 7, 9]);
+        assert_eq!(runs, vec![Run { start: 0, value: 5, length: 1 },
+                              Run { start: 1, value: 7, length: 65538 },
+                              Run { start: 65539, value: 9, length: 1 }]);
+        assert_eq!(expand(&runs).count(), 65540);
+        assert_eq!(expand(&runs).nth(65538), Some((65538, 7)));
+        assert_eq!(expand(&runs).last(), Some((65539, 9)));
+    }
+
+    #[test]
+    fn expand_runs() {
+        let runs = decode(&[(2 << 16) | 5, 7]);
+        assert_eq!(expand(&runs).collect::<Vec<_>>(), vec![(0, 5), (1, 5), (2, 5), (3, 7)]);
+        assert_eq!(expand(&[]).count(), 0);
+    }
+
+    #[test]
+    fn from_samples_round_trip() {
+        let samples = [1, 1, 2, 2, 2, 1];
+        let runs = from_samples(&samples);
+        assert_eq!(runs.len(), 3);
+        let expanded: Vec<u16> = expand(&runs).map(|(_, value)| value).collect();
+        assert_eq!(expanded, samples);
+    }
+}
@@ -0,0 +1,451 @@
+use error::{Error, Result};
+use la_channels::check_channel;
+use la_decode::{self, level, Decoder};
+
+/// SPI clock polarity and phase.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpiMode {
+    /// CPOL = 0, CPHA = 0.
+    Mode0,
+    /// CPOL = 0, CPHA = 1.
+    Mode1,
+    /// CPOL = 1, CPHA = 0.
+    Mode2,
+    /// CPOL = 1, CPHA = 1.
+    Mode3,
+}
+
+impl SpiMode {
+    /// Clock idles high.
+    pub fn cpol(&self) -> bool {
+        *self == SpiMode::Mode2 || *self == SpiMode::Mode3
+    }
+
+    /// Data is sampled on the trailing clock edge.
+    pub fn cpha(&self) -> bool {
+        *self == SpiMode::Mode1 || *self == SpiMode::Mode3
+    }
+}
+
+/// Order of the bits in a word on the wire.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+/// A word exchanged on MOSI and MISO.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpiWord {
+    /// Sample index of the clock edge sampling the first bit.
+    pub start: usize,
+    pub mosi: u32,
+    pub miso: u32,
+}
+
+/// Words exchanged while chip select was active.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpiTransaction {
+    /// Sample index chip select became active.
+    pub start: usize,
+    /// Sample index chip select became inactive, or the capture ended.
+    pub end: usize,
+    /// Time chip select became active in seconds.
+    pub time: f32,
+    pub words: Vec<SpiWord>,
+}
+
+/// Problem with the capture preventing a reliable decode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpiWarning {
+    /// The shortest clock half period has the given number of samples,
+    /// at least two are needed.
+    Undersampled(usize),
+}
+
+/// Result of decoding a capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpiDecode {
+    pub transactions: Vec<SpiTransaction>,
+    pub warnings: Vec<SpiWarning>,
+}
+
+/// SPI decoder for logic analyzer channels.
+///
+/// Defaults to mode 0, MSB first, 8 bit words and active low chip select.
+/// Without a chip select channel the whole capture is one transaction.
+pub struct Spi {
+    sclk: usize,
+    mosi: usize,
+    miso: Option<usize>,
+    cs: Option<usize>,
+    mode: SpiMode,
+    bit_order: BitOrder,
+    word_size: u32,
+    cs_active_high: bool,
+}
+
+impl Spi {
+    /// Create a decoder for the clock and MOSI channels.
+    pub fn new(sclk: usize, mosi: usize) -> Result<Self> {
+        check_channel(sclk)?;
+        check_channel(mosi)?;
+        Ok(Spi {
+            sclk,
+            mosi,
+            miso: None,
+            cs: None,
+            mode: SpiMode::Mode0,
+            bit_order: BitOrder::MsbFirst,
+            word_size: 8,
+            cs_active_high: false,
+        })
+    }
+
+    pub fn set_miso(&mut self, miso: Option<usize>) -> Result<()> {
+        if let Some(channel) = miso {
+            check_channel(channel)?;
+        }
+        self.miso = miso;
+        Ok(())
+    }
+
+    pub fn set_cs(&mut self, cs: Option<usize>) -> Result<()> {
+        if let Some(channel) = cs {
+            check_channel(channel)?;
+        }
+        self.cs = cs;
+        Ok(())
+    }
+
+    pub fn set_mode(&mut self, mode: SpiMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.bit_order = bit_order;
+    }
+
+    /// Set the number of bits per word.
+    ///
+    /// Fails with `Error::InvalidValue` if `word_size` is not 1 to 32.
+    pub fn set_word_size(&mut self, word_size: u32) -> Result<()> {
+        if !(1..=32).contains(&word_size) {
+            return Err(Error::InvalidValue("word size", word_size));
+        }
+        self.word_size = word_size;
+        Ok(())
+    }
+
+    pub fn set_cs_active_high(&mut self, active_high: bool) {
+        self.cs_active_high = active_high;
+    }
+
+    /// Sample ranges chip select is active.
+    fn segments(&self, samples: &[u16]) -> Vec<(usize, usize)> {
+        let cs = match self.cs {
+            Some(cs) => cs,
+            None => return vec![(0, samples.len())],
+        };
+        let mut segments = Vec::new();
+        let mut start = None;
+        for (i, sample) in samples.iter().enumerate() {
+            let active = level(*sample, cs) == self.cs_active_high;
+            match (active, start) {
+                (true, None) => start = Some(i),
+                (false, Some(s)) => {
+                    segments.push((s, i));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            segments.push((s, samples.len()));
+        }
+        segments
+    }
+
+    /// Words sampled between `start` and `end`.
+    fn words(&self, samples: &[u16], start: usize, end: usize) -> Vec<SpiWord> {
+        // Data is sampled on rising edges in modes 0 and 3.
+        let sample_on = self.mode.cpol() == self.mode.cpha();
+        let mut words = Vec::new();
+        let (mut mosi, mut miso, mut bits, mut first) = (0u32, 0u32, 0, 0);
+        for (i, rising) in la_decode::edges(&samples[start..end], self.sclk) {
+            if rising != sample_on {
+                continue;
+            }
+            let i = start + i;
+            if bits == 0 {
+                first = i;
+            }
+            let mosi_bit = level(samples[i], self.mosi) as u32;
+            let miso_bit = self.miso.map_or(0, |ch| level(samples[i], ch) as u32);
+            match self.bit_order {
+                BitOrder::MsbFirst => {
+                    mosi = mosi << 1 | mosi_bit;
+                    miso = miso << 1 | miso_bit;
+                }
+                BitOrder::LsbFirst => {
+                    mosi |= mosi_bit << bits;
+                    miso |= miso_bit << bits;
+                }
+            }
+            bits += 1;
+            if bits == self.word_size {
+                words.push(SpiWord { start: first, mosi, miso });
+                mosi = 0;
+                miso = 0;
+                bits = 0;
+            }
+        }
+        words
+    }
+}
+
+impl Decoder for Spi {
+    type Output = SpiDecode;
+
+    /// Decode transactions from a capture sampled at `sample_rate` Hz.
+    ///
+    /// Words cut off by chip select or the end of the capture are dropped.
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> SpiDecode {
+        let segments = self.segments(samples);
+        let mut warnings = Vec::new();
+        let min_half_period = segments.iter()
+            .filter_map(|&(start, end)| {
+                let edges: Vec<usize> = la_decode::edges(&samples[start..end], self.sclk)
+                    .map(|(i, _)| i)
+                    .collect();
+                edges.windows(2).map(|pair| pair[1] - pair[0]).min()
+            })
+            .min();
+        if let Some(half_period) = min_half_period {
+            if half_period < 2 {
+                warnings.push(SpiWarning::Undersampled(half_period));
+                return SpiDecode { transactions: Vec::new(), warnings };
+            }
+        }
+
+        let transactions = segments.into_iter()
+            .map(|(start, end)| SpiTransaction {
+                start,
+                end,
+                time: la_decode::to_seconds(start, sample_rate),
+                words: self.words(samples, start, end),
+            })
+            .collect();
+        SpiDecode { transactions, warnings }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Capture builder with SCLK on channel 0, MOSI on 1, MISO on 2 and
+    /// chip select on 3.
+    struct Bus {
+        samples: Vec<u16>,
+        mode: SpiMode,
+        /// Samples per clock half period.
+        half: usize,
+        cs_active_high: bool,
+        selected: bool,
+    }
+
+    impl Bus {
+        fn new(mode: SpiMode, half: usize) -> Self {
+            Bus { samples: Vec::new(), mode, half, cs_active_high: false, selected: false }
+        }
+
+        fn active_high() -> Self {
+            Bus { cs_active_high: true, ..Bus::new(SpiMode::Mode0, 4) }
+        }
+
+        fn level(&mut self, sclk: bool, mosi: u16, miso: u16, samples: usize) {
+            let cs = (self.selected == self.cs_active_high) as u16;
+            self.samples.extend(vec![sclk as u16 | mosi << 1 | miso << 2 | cs << 3; samples]);
+        }
+
+        /// Hold the clock idle for a clock period.
+        fn idle(&mut self) {
+            let (cpol, half) = (self.mode.cpol(), self.half);
+            self.level(cpol, 0, 0, 2 * half);
+        }
+
+        fn select(&mut self) {
+            self.idle();
+            self.selected = true;
+            self.idle();
+        }
+
+        fn deselect(&mut self) {
+            self.idle();
+            self.selected = false;
+            self.idle();
+        }
+
+        /// Clock out one bit on MOSI and MISO.
+        fn bit(&mut self, mosi: u16, miso: u16) {
+            let (cpol, half) = (self.mode.cpol(), self.half);
+            // With CPHA = 0 data is set up before the leading edge, with
+            // CPHA = 1 it changes on the leading edge.
+            let first = cpol != self.mode.cpha();
+            self.level(first, mosi, miso, half);
+            self.level(!first, mosi, miso, half);
+        }
+
+        fn word(&mut self, mosi: u32, miso: u32, bits: u32, order: BitOrder) {
+            for n in 0..bits {
+                let n = match order {
+                    BitOrder::MsbFirst => bits - 1 - n,
+                    BitOrder::LsbFirst => n,
+                };
+                self.bit((mosi >> n) as u16 & 1, (miso >> n) as u16 & 1);
+            }
+        }
+
+        fn spi(&self) -> Spi {
+            let mut spi = Spi::new(0, 1).unwrap();
+            spi.set_miso(Some(2)).unwrap();
+            spi.set_cs(Some(3)).unwrap();
+            spi.set_mode(self.mode);
+            spi.set_cs_active_high(self.cs_active_high);
+            spi
+        }
+    }
+
+    fn words(decode: &SpiDecode) -> Vec<Vec<(u32, u32)>> {
+        assert!(decode.warnings.is_empty(), "{:?}", decode.warnings);
+        decode.transactions.iter()
+            .map(|transaction| transaction.words.iter().map(|word| (word.mosi, word.miso)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn modes() {
+        for &mode in &[SpiMode::Mode0, SpiMode::Mode1, SpiMode::Mode2, SpiMode::Mode3] {
+            let mut bus = Bus::new(mode, 3);
+            bus.select();
+            bus.word(0xa5, 0x3c, 8, BitOrder::MsbFirst);
+            bus.word(0x01, 0x80, 8, BitOrder::MsbFirst);
+            bus.deselect();
+            let decode = bus.spi().decode(&bus.samples, 1e6);
+            assert_eq!(words(&decode), vec![vec![(0xa5, 0x3c), (0x01, 0x80)]], "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn without_miso_and_cs() {
+        let mut bus = Bus::new(SpiMode::Mode0, 2);
+        bus.selected = true;
+        bus.idle();
+        bus.word(0x5a, 0xff, 8, BitOrder::MsbFirst);
+        let spi = Spi::new(0, 1).unwrap();
+        let decode = spi.decode(&bus.samples, 1e6);
+        assert_eq!(words(&decode), vec![vec![(0x5a, 0)]]);
+        assert_eq!((decode.transactions[0].start, decode.transactions[0].end), (0, bus.samples.len()));
+    }
+
+    #[test]
+    fn cs_framing() {
+        let mut bus = Bus::new(SpiMode::Mode0, 2);
+        // Clock edges while deselected belong to another device.
+        bus.word(0xff, 0xff, 8, BitOrder::MsbFirst);
+        let start = bus.samples.len() + 2 * bus.half;
+        bus.select();
+        bus.word(0x12, 0x34, 8, BitOrder::MsbFirst);
+        // A word cut off by chip select is dropped.
+        bus.word(0x0f, 0x0f, 4, BitOrder::MsbFirst);
+        let end = bus.samples.len() + 2 * bus.half;
+        bus.deselect();
+        bus.select();
+        bus.word(0x56, 0x78, 8, BitOrder::MsbFirst);
+        bus.idle();
+        let decode = bus.spi().decode(&bus.samples, 1e6);
+        assert_eq!(words(&decode), vec![vec![(0x12, 0x34)], vec![(0x56, 0x78)]]);
+        assert_eq!((decode.transactions[0].start, decode.transactions[0].end), (start, end));
+        // The last transaction lasts until the end of the capture.
+        assert_eq!(decode.transactions[1].end, bus.samples.len());
+        assert_eq!(decode.transactions[0].time, start as f32 * 1e-6);
+    }
+
+    #[test]
+    fn cs_active_high() {
+        let mut bus = Bus::active_high();
+        bus.select();
+        bus.word(0xc3, 0x3c, 8, BitOrder::MsbFirst);
+        bus.deselect();
+        assert_eq!(bus.samples[0] & 8, 0);
+        assert_eq!(words(&bus.spi().decode(&bus.samples, 1e6)), vec![vec![(0xc3, 0x3c)]]);
+        let mut spi = bus.spi();
+        spi.set_cs_active_high(false);
+        assert!(words(&spi.decode(&bus.samples, 1e6)).iter().all(|words| words.is_empty()));
+    }
+
+    #[test]
+    fn lsb_first() {
+        let mut bus = Bus::new(SpiMode::Mode3, 2);
+        bus.select();
+        bus.word(0x01, 0x80, 8, BitOrder::LsbFirst);
+        bus.deselect();
+        let mut spi = bus.spi();
+        spi.set_bit_order(BitOrder::LsbFirst);
+        assert_eq!(words(&spi.decode(&bus.samples, 1e6)), vec![vec![(0x01, 0x80)]]);
+        spi.set_bit_order(BitOrder::MsbFirst);
+        assert_eq!(words(&spi.decode(&bus.samples, 1e6)), vec![vec![(0x80, 0x01)]]);
+    }
+
+    #[test]
+    fn word_sizes() {
+        let mut bus = Bus::new(SpiMode::Mode0, 2);
+        bus.select();
+        bus.word(0xabc, 0x123, 12, BitOrder::MsbFirst);
+        bus.word(0x5, 0x2, 3, BitOrder::MsbFirst);
+        bus.word(0xdead_beef, 0x0123_4567, 32, BitOrder::MsbFirst);
+        bus.deselect();
+        let mut spi = bus.spi();
+        spi.set_word_size(12).unwrap();
+        let decode = spi.decode(&bus.samples, 1e6);
+        assert_eq!(words(&decode)[0][0], (0xabc, 0x123));
+        // 12 + 3 + 32 bits are not a multiple of 12, the last word is dropped.
+        assert_eq!(words(&decode)[0].len(), 3);
+
+        let mut bus = Bus::new(SpiMode::Mode0, 2);
+        bus.select();
+        bus.word(0x5, 0x2, 3, BitOrder::MsbFirst);
+        bus.word(0xdead_beef, 0x0123_4567, 32, BitOrder::MsbFirst);
+        bus.deselect();
+        spi.set_word_size(3).unwrap();
+        let decode = spi.decode(&bus.samples, 1e6);
+        assert_eq!(words(&decode)[0][0], (0x5, 0x2));
+        assert_eq!(words(&decode)[0].len(), 11);
+
+        let mut bus = Bus::new(SpiMode::Mode0, 2);
+        bus.select();
+        bus.word(0xdead_beef, 0x0123_4567, 32, BitOrder::MsbFirst);
+        bus.deselect();
+        spi.set_word_size(32).unwrap();
+        assert_eq!(words(&spi.decode(&bus.samples, 1e6)), vec![vec![(0xdead_beef, 0x0123_4567)]]);
+
+        for &size in &[0, 33] {
+            match spi.set_word_size(size) {
+                Err(Error::InvalidValue(_, value)) => assert_eq!(value, size),
+                _ => panic!("word size {} should be rejected", size),
+            }
+        }
+    }
+
+    #[test]
+    fn undersampled() {
+        let mut bus = Bus::new(SpiMode::Mode0, 1);
+        bus.select();
+        bus.word(0xa5, 0x3c, 8, BitOrder::MsbFirst);
+        bus.deselect();
+        let decode = bus.spi().decode(&bus.samples, 1e6);
+        assert_eq!(decode.warnings, vec![SpiWarning::Undersampled(1)]);
+        assert!(decode.transactions.is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+use error::{Error, Result};
+use la_channels::check_channel;
+use la_decode::{self, level, Decoder};
+
+/// Standard baud rates autodetection snaps to.
+pub const BAUD_RATES: [u32; 14] = [
+    300, 600, 1200, 2400, 4800, 9600, 14400, 19200, 38400, 57600,
+    115200, 230400, 460800, 921600,
+];
+
+/// Parity bit of a UART frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Number of stop bits of a UART frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    OneAndHalf,
+    Two,
+}
+
+impl StopBits {
+    fn bits(&self) -> f32 {
+        match *self {
+            StopBits::One => 1.0,
+            StopBits::OneAndHalf => 1.5,
+            StopBits::Two => 2.0,
+        }
+    }
+
+    /// Number of stop bits sampled in their middle.
+    ///
+    /// The half stop bit is too short to be sampled reliably.
+    fn sampled(&self) -> u32 {
+        match *self {
+            StopBits::One | StopBits::OneAndHalf => 1,
+            StopBits::Two => 2,
+        }
+    }
+}
+
+/// A decoded UART frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UartFrame {
+    /// Sample index of the start bit edge.
+    pub start: usize,
+    /// Sample index of the end of the last stop bit.
+    pub end: usize,
+    /// Time of the start bit edge in seconds.
+    pub time: f32,
+    /// Data bits, LSB first on the wire.
+    pub data: u16,
+    /// Parity bit does not match the data.
+    pub parity_error: bool,
+    /// A stop bit was not at idle level.
+    pub framing_error: bool,
+}
+
+/// UART decoder for one logic analyzer channel.
+///
+/// Defaults to 8 data bits, no parity, one stop bit and idle high.
+pub struct Uart {
+    channel: usize,
+    baud: u32,
+    data_bits: u32,
+    parity: Parity,
+    stop_bits: StopBits,
+    inverted: bool,
+}
+
+impl Uart {
+    /// Create a decoder for `channel` at `baud` bits per second.
+    ///
+    /// Fails with `Error::InvalidValue` if `baud` is 0.
+    pub fn new(channel: usize, baud: u32) -> Result<Self> {
+        check_channel(channel)?;
+        if baud == 0 {
+            return Err(Error::InvalidValue("baud rate", baud));
+        }
+        Ok(Uart {
+            channel,
+            baud,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            inverted: false,
+        })
+    }
+
+    /// Set the number of data bits (5 to 9).
+    pub fn set_data_bits(&mut self, data_bits: u32) {
+        self.data_bits = data_bits.clamp(5, 9);
+    }
+
+    pub fn set_parity(&mut self, parity: Parity) {
+        self.parity = parity;
+    }
+
+    pub fn set_stop_bits(&mut self, stop_bits: StopBits) {
+        self.stop_bits = stop_bits;
+    }
+
+    /// Set inverted polarity, the line idles low.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+}
+
+impl Decoder for Uart {
+    type Output = Vec<UartFrame>;
+
+    /// Decode frames from a capture sampled at `sample_rate` Hz.
+    ///
+    /// Bits are sampled in the middle of their period, measured from the
+    /// falling edge of the start bit. Of the stop bits only the first, and
+    /// the second of `StopBits::Two`, are sampled. Frames cut off by the end
+    /// of the capture are dropped.
+    fn decode(&self, samples: &[u16], sample_rate: f32) -> Vec<UartFrame> {
+        let bit = sample_rate / self.baud as f32;
+        let line = |i: usize| level(samples[i], self.channel) != self.inverted;
+        let at = |start: usize, bits: f32| start + (bits * bit) as usize;
+        let parity_bits = if self.parity == Parity::None { 0 } else { 1 };
+        let frame_bits = (1 + self.data_bits + parity_bits) as f32 + self.stop_bits.bits();
+        // Middle of the last sampled stop bit.
+        let last_bit = (self.data_bits + parity_bits + self.stop_bits.sampled()) as f32 + 0.5;
+
+        let mut frames = Vec::new();
+        // Wait for the idle level before looking for a start bit.
+        let mut i = match (0..samples.len()).find(|i| line(*i)) {
+            Some(i) => i,
+            None => return frames,
+        };
+        while i + 1 < samples.len() {
+            if !line(i) || line(i + 1) {
+                i += 1;
+                continue;
+            }
+            let start = i + 1;
+            if at(start, frame_bits) > samples.len() || at(start, last_bit) >= samples.len() {
+                break;
+            }
+            // Glitch instead of a start bit.
+            if line(at(start, 0.5)) {
+                i = start;
+                continue;
+            }
+            let mut data = 0u16;
+            for n in 0..self.data_bits {
+                if line(at(start, 1.5 + n as f32)) {
+                    data |= 1 << n;
+                }
+            }
+            let mut pos = 1.5 + self.data_bits as f32;
+            let parity_error = match self.parity {
+                Parity::None => false,
+                parity => {
+                    let ones = data.count_ones() + line(at(start, pos)) as u32;
+                    pos += 1.0;
+                    (ones % 2 == 1) != (parity == Parity::Odd)
+                }
+            };
+            let framing_error = (0..self.stop_bits.sampled())
+                .any(|stop| !line(at(start, pos + stop as f32)));
+            frames.push(UartFrame {
+                start,
+                end: at(start, frame_bits),
+                time: la_decode::to_seconds(start, sample_rate),
+                data,
+                parity_error,
+                framing_error,
+            });
+            // Resynchronize in the middle of the first stop bit.
+            i = at(start, pos);
+        }
+        frames
+    }
+}
+
+/// Estimate the baud rate of `channel` from the shortest pulse.
+///
+/// Snaps to a standard rate within 5%, returns `None` if the channel has
+/// fewer than two edges.
+pub fn detect_baud(samples: &[u16], channel: usize, sample_rate: f32) -> Option<u32> {
+    let edges: Vec<usize> = la_decode::edges(samples, channel).map(|(i, _)| i).collect();
+    let shortest = edges.windows(2).map(|pair| pair[1] - pair[0]).min()?;
+    let baud = sample_rate / shortest as f32;
+    let standard = BAUD_RATES.iter()
+        .cloned()
+        .find(|rate| ((*rate as f32 - baud) / *rate as f32).abs() < 0.05);
+    Some(standard.unwrap_or(baud.round() as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples per bit of the test captures.
+    const SPB: usize = 10;
+
+    /// Encode `bytes` on channel 0 back to back, with stop bits lasting
+    /// `stop` bit periods.
+    fn encode(bytes: &[u8], stop: f32) -> Vec<u16> {
+        encode_parity(bytes, Parity::None, false, stop)
+    }
+
+    /// Encode `bytes` with a `parity` bit, flipped if `bad_parity` is set.
+    fn encode_parity(bytes: &[u8], parity: Parity, bad_parity: bool, stop: f32) -> Vec<u16> {
+        let mut samples = vec![1; SPB];
+        for byte in bytes {
+            samples.extend(vec![0; SPB]);
+            for n in 0..8 {
+                samples.extend(vec![(*byte as u16 >> n) & 1; SPB]);
+            }
+            if parity != Parity::None {
+                let odd = byte.count_ones() % 2 == 1;
+                let bit = (odd != (parity == Parity::Odd)) != bad_parity;
+                samples.extend(vec![bit as u16; SPB]);
+            }
+            samples.extend(vec![1; (stop * SPB as f32) as usize]);
+        }
+        samples
+    }
+
+    fn decode(uart: &Uart, samples: &[u16]) -> Vec<(u16, bool)> {
+        uart.decode(samples, (9600 * SPB) as f32).iter()
+            .map(|frame| (frame.data, frame.framing_error))
+            .collect()
+    }
+
+    #[test]
+    fn one_and_half_stop_bits() {
+        let mut uart = Uart::new(0, 9600).unwrap();
+        uart.set_stop_bits(StopBits::OneAndHalf);
+        // The last frame ends exactly at the end of the capture.
+        let samples = encode(&[0x55, 0xa3, 0x0f], 1.5);
+        assert_eq!(decode(&uart, &samples), vec![(0x55, false), (0xa3, false), (0x0f, false)]);
+    }
+
+    #[test]
+    fn two_stop_bits() {
+        let mut uart = Uart::new(0, 9600).unwrap();
+        uart.set_stop_bits(StopBits::Two);
+        let samples = encode(&[0x12, 0x34], 2.0);
+        assert_eq!(decode(&uart, &samples), vec![(0x12, false), (0x34, false)]);
+        // The next start bit follows after a single stop bit.
+        let samples = encode(&[0x12, 0x34], 1.0);
+        assert_eq!(decode(&uart, &samples)[0], (0x12, true));
+    }
+
+    #[test]
+    fn invalid_channel() {
+        match Uart::new(16, 9600) {
+            Err(Error::InvalidChannel(16)) => {}
+            _ => panic!("channel out of range should be rejected"),
+        }
+        assert_eq!(detect_baud(&encode(&[0x55], 1.0), 16, 96000.0), None);
+    }
+
+    #[test]
+    fn frame_cut_off() {
+        let uart = Uart::new(0, 9600).unwrap();
+        let samples = encode(&[0x12, 0x34], 1.0);
+        assert_eq!(decode(&uart, &samples), vec![(0x12, false), (0x34, false)]);
+        assert_eq!(decode(&uart, &samples[..samples.len() - SPB / 2]), vec![(0x12, false)]);
+    }
+
+    #[test]
+    fn invalid_baud() {
+        match Uart::new(0, 0) {
+            Err(Error::InvalidValue(_, 0)) => {}
+            _ => panic!("baud rate 0 should be rejected"),
+        }
+    }
+
+    #[test]
+    fn parity() {
+        for &parity in &[Parity::Even, Parity::Odd] {
+            let mut uart = Uart::new(0, 9600).unwrap();
+            uart.set_parity(parity);
+            let errors = |samples: &[u16]| -> Vec<(u16, bool, bool)> {
+                uart.decode(samples, (9600 * SPB) as f32).iter()
+                    .map(|frame| (frame.data, frame.parity_error, frame.framing_error))
+                    .collect()
+            };
+            let samples = encode_parity(&[0x00, 0x01, 0xa3], parity, false, 1.0);
+            assert_eq!(errors(&samples),
+                       vec![(0x00, false, false), (0x01, false, false), (0xa3, false, false)]);
+            let samples = encode_parity(&[0x00, 0x01, 0xa3], parity, true, 1.0);
+            assert_eq!(errors(&samples),
+                       vec![(0x00, true, false), (0x01, true, false), (0xa3, true, false)]);
+        }
+    }
+
+    #[test]
+    fn inverted() {
+        let samples: Vec<u16> = encode(&[0x12, 0xf0], 1.0).iter().map(|sample| sample ^ 1).collect();
+        let mut uart = Uart::new(0, 9600).unwrap();
+        uart.set_inverted(true);
+        assert_eq!(decode(&uart, &samples), vec![(0x12, false), (0xf0, false)]);
+        // Without inversion the frames are not recovered.
+        uart.set_inverted(false);
+        assert_ne!(decode(&uart, &samples), vec![(0x12, false), (0xf0, false)]);
+    }
+
+    #[test]
+    fn baud_detection() {
+        let samples = encode(&[0x55], 1.0);
+        assert_eq!(detect_baud(&samples, 0, (9600 * SPB) as f32), Some(9600));
+        // Within 5% of a standard rate.
+        assert_eq!(detect_baud(&samples, 0, 9600.0 * SPB as f32 * 1.03), Some(9600));
+        assert_eq!(detect_baud(&samples, 0, 115200.0 * SPB as f32 * 0.97), Some(115200));
+        // Too far off to snap.
+        assert_eq!(detect_baud(&samples, 0, 9600.0 * SPB as f32 * 1.2), Some(11520));
+        assert_eq!(detect_baud(&[1; 100], 0, (9600 * SPB) as f32), None);
+    }
+}
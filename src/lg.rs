@@ -0,0 +1,345 @@
+use board::{BoardFamily, BoardInfo, Module};
+use burst::*;
+use compat::{self, Compat};
+use error::{Error, Result};
+use event::*;
+use lg_output::*;
+use lock::{DeviceLock, LockMode, Shared};
+use sysfs::Sysfs;
+use uio::*;
+use volatile_register::RW;
+
+pub const BUFFER_SIZE: u32 = 0x4000; // 2**14 = 16384
+/// Sampling frequency of the STEMlab 125-14, see `BoardInfo`.
+pub const SAMPLING_FREQUENCY: u32 = 125_000_000;
+
+#[repr(C)]
+pub struct LgRegs {
+    event: EventRegs,
+    _rsv0: u32,
+    burst: BurstRegs,
+    output: LgOutputRegs,
+}
+
+impl Default for LgRegs {
+    /// Set registers to default (power-up) state.
+    fn default(&mut self) {
+        self.event.default();
+        self.burst.default();
+        self.output.default();
+    }
+}
+
+impl Show for LgRegs {
+    /// Print FPGA module registers.
+    fn show(&self) {
+        self.event.show();
+        self.burst.show();
+        self.output.show();
+    }
+}
+
+pub struct LogicGenerator {
+    regs: Box<dyn Mapping>,
+    buffer: Box<dyn Mapping>,
+    board: BoardInfo,
+    _lock: Option<DeviceLock>,
+    compat: Compat,
+}
+
+impl LogicGenerator {
+    /// Open the logic generator, failing if another process is using it.
+    pub fn new() -> Result<Self> {
+        LogicGenerator::with_lock(LockMode::Try)
+    }
+
+    /// Open the logic generator, locking it against other processes with `mode`.
+    pub fn with_lock(mode: LockMode) -> Result<Self> {
+        LogicGenerator::with_sysfs(&Sysfs::new(), mode)
+    }
+
+    /// Open the logic generator found through `sysfs`, locking it with `mode`.
+    pub fn with_sysfs(sysfs: &Sysfs, mode: LockMode) -> Result<Self> {
+        LogicGenerator::open(sysfs, |uio_num| DeviceLock::acquire(sysfs, uio_num, mode))
+    }
+
+    /// Open the logic generator read-only, sharing it with other read-only users.
+    ///
+    /// Fails with `Error::Busy` if another process holds an exclusive lock.
+    pub fn shared() -> Result<Shared<Self>> {
+        LogicGenerator::shared_with_sysfs(&Sysfs::new())
+    }
+
+    /// Open the logic generator found through `sysfs` read-only, see `shared`.
+    pub fn shared_with_sysfs(sysfs: &Sysfs) -> Result<Shared<Self>> {
+        LogicGenerator::open(sysfs, |uio_num| DeviceLock::acquire_shared(sysfs, uio_num))
+            .map(Shared::new)
+    }
+
+    fn open<F: FnOnce(usize) -> Result<DeviceLock>>(sysfs: &Sysfs, lock: F) -> Result<Self> {
+        let compat = compat::check(sysfs)?;
+        let board = BoardInfo::detect(sysfs)?;
+        board.require(Module::Lg)?;
+        let uio = open_uio(sysfs, "lg")?;
+        let lock = lock(uio.num)?;
+        let regs = map_mapping(sysfs, &uio, 0)?;
+        let buffer = map_mapping(sysfs, &uio, 1)?;
+        let mut lg = LogicGenerator::from_mappings(regs, buffer)?;
+        lg.set_board(board);
+        lg._lock = Some(lock);
+        lg.compat = compat;
+        Ok(lg)
+    }
+
+    /// Create a logic generator backed by arbitrary register and buffer memory.
+    ///
+    /// Assumes a STEMlab 125-14, see `set_board`.
+    pub fn from_mappings(regs: Box<dyn Mapping>, buffer: Box<dyn Mapping>) -> Result<Self> {
+        check_size::<LgRegs>(&*regs)?;
+        check_size::<<Self as BufferDevice>::Buffer>(&*buffer)?;
+        let board = BoardInfo::with_family(BoardFamily::Stemlab125_14);
+        Ok(LogicGenerator { regs, buffer, board, _lock: None, compat: Compat::Unchecked })
+    }
+
+    /// Outcome of the compatibility check of the bitstream when the device
+    /// was opened, `Compat::Unchecked` for devices created from mappings.
+    pub fn compat(&self) -> Compat {
+        self.compat
+    }
+
+    /// Board the sample rate is taken from.
+    pub fn board(&self) -> BoardInfo {
+        self.board
+    }
+
+    /// Set the board the sample rate is taken from.
+    pub fn set_board(&mut self, board: BoardInfo) {
+        self.board = board;
+    }
+
+    /// Upload a pattern to the generator buffer.
+    ///
+    /// Each sample holds the state of all 16 pins. The burst data length is
+    /// set to the length of the pattern and the burst period is extended
+    /// if it is shorter than the pattern.
+    pub fn set_pattern(&mut self, pattern: &[u16]) -> Result<()> {
+        if pattern.is_empty() {
+            return Err(Error::EmptyBuffer);
+        }
+        if pattern.len() > BUFFER_SIZE as usize {
+            return Err(Error::BufferOverflow(pattern.len(), BUFFER_SIZE as usize));
+        }
+        for (reg, sample) in self.mut_buffer().iter_mut().zip(pattern) {
+            unsafe { reg.write(*sample); }
+        }
+        let len = pattern.len() as u32;
+        self.set_burst_data_length(len)?;
+        if self.burst_period_length() < len {
+            self.set_burst_period_length(len)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the sample rate depending on decimation factor.
+    pub fn sample_rate(&self) -> f32 {
+        self.board.sampling_frequency() as f32 / self.decimation() as f32
+    }
+
+    /// Set the decimation factor closest to the requested sample rate.
+    pub fn set_sample_rate(&mut self, rate: f32) {
+        self.set_decimation((self.board.sampling_frequency() as f32 / rate).round() as u32);
+    }
+
+    /// Returns the sample period depending on decimation factor.
+    pub fn sample_period(&self) -> f32 {
+        1.0 / self.sample_rate()
+    }
+}
+
+unsafe impl RegDevice for LogicGenerator {
+    type Registers = LgRegs;
+
+    fn reg_mmap(&self) -> &dyn Mapping {
+        &*self.regs
+    }
+}
+
+unsafe impl BufferDevice for LogicGenerator {
+    type Buffer = [RW<u16>; BUFFER_SIZE as usize];
+
+    fn buffer_mmap(&self) -> &dyn Mapping {
+        &*self.buffer
+    }
+}
+
+impl Default for LogicGenerator {
+    fn default(&mut self) {
+        self.mut_regs().default();
+    }
+}
+
+impl Show for LogicGenerator {
+    fn show(&self) {
+        self.regs().show();
+    }
+}
+
+impl EventRegsAPI for LogicGenerator {
+    fn reset(&mut self) {
+        self.mut_regs().event.reset();
+    }
+    fn start(&mut self) {
+        self.mut_regs().event.start();
+    }
+    fn stop(&mut self) {
+        self.mut_regs().event.stop();
+    }
+    fn trigger(&mut self) {
+        self.mut_regs().event.trigger();
+    }
+    fn start_trigger(&mut self) {
+        self.mut_regs().event.start_trigger();
+    }
+    fn is_running(&self) -> bool {
+        self.regs().event.is_running()
+    }
+    fn is_triggered(&self) -> bool {
+        self.regs().event.is_triggered()
+    }
+    fn sync_source(&self) -> Result<SyncSource> {
+        self.regs().event.sync_source()
+    }
+    fn set_sync_source(&mut self, event: SyncSource) {
+        self.mut_regs().event.set_sync_source(event);
+    }
+    fn trigger_source(&self) -> TriggerSources {
+        self.regs().event.trigger_source()
+    }
+    fn set_trigger_source<T: Into<TriggerSources>>(&mut self, trigger: T) {
+        self.mut_regs().event.set_trigger_source(trigger);
+    }
+}
+
+impl BurstRegsAPI for LogicGenerator {
+    fn burst(&self) -> bool {
+        self.regs().burst.burst()
+    }
+    fn set_burst(&mut self, burst: bool) {
+        self.mut_regs().burst.set_burst(burst);
+    }
+    fn burst_infinite(&self) -> bool {
+        self.regs().burst.burst_infinite()
+    }
+    fn set_burst_infinite(&mut self, infinite: bool) {
+        self.mut_regs().burst.set_burst_infinite(infinite);
+    }
+    fn burst_data_length(&self) -> u32 {
+        self.regs().burst.burst_data_length()
+    }
+    fn set_burst_data_length(&mut self, len: u32) -> Result<()> {
+        self.mut_regs().burst.set_burst_data_length(len)
+    }
+    fn burst_period_length(&self) -> u32 {
+        self.regs().burst.burst_period_length()
+    }
+    fn set_burst_period_length(&mut self, len: u32) -> Result<()> {
+        self.mut_regs().burst.set_burst_period_length(len)
+    }
+    fn burst_repetitions(&self) -> u32 {
+        self.regs().burst.burst_repetitions()
+    }
+    fn set_burst_repetitions(&mut self, num: u32) -> Result<()> {
+        self.mut_regs().burst.set_burst_repetitions(num)
+    }
+    fn burst_length_status(&self) -> u32 {
+        self.regs().burst.burst_length_status()
+    }
+    fn burst_repetitions_status(&self) -> u32 {
+        self.regs().burst.burst_repetitions_status()
+    }
+}
+
+impl LgOutputRegsAPI for LogicGenerator {
+    fn output_enable(&self) -> u32 {
+        self.regs().output.output_enable()
+    }
+    fn set_output_enable(&mut self, mask: u32) {
+        self.mut_regs().output.set_output_enable(mask);
+    }
+    fn open_drain(&self) -> u32 {
+        self.regs().output.open_drain()
+    }
+    fn set_open_drain(&mut self, mask: u32) {
+        self.mut_regs().output.set_open_drain(mask);
+    }
+    fn decimation(&self) -> u32 {
+        self.regs().output.decimation()
+    }
+    fn set_decimation(&mut self, dec: u32) {
+        self.mut_regs().output.set_decimation(dec);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error::Error;
+    use fake::{self, FakeMemory, FakeSysfs};
+    use super::*;
+
+    fn logic_generator() -> LogicGenerator {
+        LogicGenerator::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                      Box::new(FakeMemory::new(BUFFER_SIZE as usize * 2))).unwrap()
+    }
+
+    #[test]
+    fn pattern() {
+        let mut lg = logic_generator();
+        lg.set_pattern(&[1, 2, 3]).unwrap();
+        assert_eq!(fake::read_u16(lg.buffer_mmap(), 4), 3);
+        assert_eq!(lg.burst_data_length(), 3);
+        assert_eq!(lg.burst_period_length(), 3);
+        match lg.set_pattern(&[]) {
+            Err(Error::EmptyBuffer) => {}
+            _ => panic!("empty pattern should be rejected"),
+        }
+    }
+
+    #[test]
+    fn decimation() {
+        let mut lg = logic_generator();
+        lg.set_sample_rate(1_000_000.0);
+        assert_eq!(fake::read(lg.reg_mmap(), 0x30), 124);
+        lg.set_decimation(0);
+        assert_eq!(fake::read(lg.reg_mmap(), 0x30), 0);
+        lg.set_sample_rate(1e9);
+        assert_eq!(lg.decimation(), 1);
+        lg.set_board(BoardInfo::with_family(BoardFamily::Signallab250_12));
+        lg.set_sample_rate(1_000_000.0);
+        assert_eq!(lg.decimation(), 250);
+    }
+
+    #[test]
+    fn open_drain() {
+        let mut lg = logic_generator();
+        lg.set_open_drain(0xff);
+        assert_eq!(fake::read(lg.reg_mmap(), 0x2c), 0xff);
+    }
+
+    #[test]
+    fn lock() {
+        let fake = FakeSysfs::new();
+        fake.add_mercury_devices(fake::HWID);
+        let sysfs = fake.sysfs();
+        let lg = LogicGenerator::with_sysfs(&sysfs, LockMode::Try).unwrap();
+        match LogicGenerator::with_sysfs(&sysfs, LockMode::Try) {
+            Err(Error::Busy(..)) => {}
+            _ => panic!("second exclusive open should fail"),
+        }
+        assert!(LogicGenerator::shared_with_sysfs(&sysfs).is_err());
+        drop(lg);
+        let first = LogicGenerator::shared_with_sysfs(&sysfs).unwrap();
+        let second = LogicGenerator::shared_with_sysfs(&sysfs).unwrap();
+        assert_eq!(first.decimation(), second.decimation());
+        assert!(LogicGenerator::with_sysfs(&sysfs, LockMode::Try).is_err());
+    }
+}
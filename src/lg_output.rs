@@ -0,0 +1,74 @@
+use std::cmp;
+use uio::*;
+use volatile_register::RW;
+
+#[repr(C)]
+pub struct LgOutputRegs {
+    /// Output enable
+    cfg_oen: RW<u32>,
+    /// Open drain
+    cfg_odr: RW<u32>,
+    /// Decimation factor
+    cfg_dec: RW<u32>,
+}
+
+impl Default for LgOutputRegs {
+    fn default(&mut self) {
+        unsafe {
+            self.cfg_oen.write(0);
+            self.cfg_odr.write(0);
+            self.cfg_dec.write(0);
+        }
+    }
+}
+
+impl Show for LgOutputRegs {
+    fn show(&self) {
+        println!("cfg_oen = {:x}", self.cfg_oen.read());
+        println!("cfg_odr = {:x}", self.cfg_odr.read());
+        println!("cfg_dec = {:x}", self.cfg_dec.read());
+    }
+}
+
+pub trait LgOutputRegsAPI {
+    /// Get output enable bit mask.
+    fn output_enable(&self) -> u32;
+    /// Set output enable bit mask.
+    ///
+    /// Pins with a cleared bit are not driven by the logic generator.
+    fn set_output_enable(&mut self, mask: u32);
+    /// Get open drain bit mask.
+    fn open_drain(&self) -> u32;
+    /// Set open drain bit mask.
+    ///
+    /// Pins with a set bit are only driven low and left floating for a
+    /// high level, pins with a cleared bit are push-pull.
+    fn set_open_drain(&mut self, mask: u32);
+    /// Get decimation factor.
+    fn decimation(&self) -> u32;
+    /// Set decimation factor.
+    ///
+    /// A factor of 0 is treated as 1, no decimation.
+    fn set_decimation(&mut self, dec: u32);
+}
+
+impl LgOutputRegsAPI for LgOutputRegs {
+    fn output_enable(&self) -> u32 {
+        self.cfg_oen.read()
+    }
+    fn set_output_enable(&mut self, mask: u32) {
+        unsafe { self.cfg_oen.write(mask); }
+    }
+    fn open_drain(&self) -> u32 {
+        self.cfg_odr.read()
+    }
+    fn set_open_drain(&mut self, mask: u32) {
+        unsafe { self.cfg_odr.write(mask); }
+    }
+    fn decimation(&self) -> u32 {
+        self.cfg_dec.read() + 1
+    }
+    fn set_decimation(&mut self, dec: u32) {
+        unsafe { self.cfg_dec.write(cmp::max(dec, 1) - 1); }
+    }
+}
@@ -1,17 +1,52 @@
 #![feature(try_from)]
 
 extern crate fs2;
-extern crate uio as _uio;
+#[cfg(feature = "async")]
+extern crate futures_core;
+extern crate libc;
+#[cfg(feature = "async")]
+extern crate tokio;
 extern crate volatile_register;
 
 pub mod acquire;
+#[cfg(feature = "async")]
+pub mod async_irq;
+pub mod board;
+pub mod burst;
+pub mod compat;
+pub mod error;
 pub mod event;
+#[cfg(any(test, feature = "fake"))]
+pub mod fake;
+pub mod firmware;
+pub mod gen;
+pub mod gen_linear;
+pub mod gen_table;
 pub mod hwid;
 pub mod interrupts;
 pub mod la;
+pub mod la_can;
+pub mod la_channels;
+pub mod la_decode;
+pub mod la_i2c;
+pub mod la_manchester;
 pub mod la_mask;
+pub mod la_measure;
+pub mod la_onewire;
 pub mod la_rle;
+pub mod la_spi;
 pub mod la_trigger;
+pub mod la_uart;
+pub mod lg;
+pub mod lg_output;
+pub mod lock;
 pub mod management;
+pub mod osc;
+pub mod osc_filter;
+pub mod osc_trigger;
 pub mod prelude;
+pub mod selftest;
+pub mod sigrok;
+pub mod sysfs;
 pub mod uio;
+pub mod vcd;
@@ -0,0 +1,113 @@
+use std::fs::{self, File};
+use std::io;
+use std::ops::Deref;
+use std::os::unix::fs::MetadataExt;
+use error::{Error, Result};
+use fs2::{self, FileExt};
+use sysfs::Sysfs;
+
+/// How to lock a device against concurrent use by other processes.
+///
+/// The locks are advisory, they only exclude processes using this crate.
+/// Devices opened with `shared()` take a shared lock instead.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockMode {
+    /// Wait until no other process holds a lock on the device.
+    Blocking,
+    /// Fail with `Error::Busy` if another process holds a lock on the device.
+    Try,
+}
+
+/// Advisory lock on a UIO device node, released when dropped.
+pub struct DeviceLock {
+    _file: File,
+}
+
+impl DeviceLock {
+    /// Lock the device node `uioN` exclusively.
+    pub fn acquire(sysfs: &Sysfs, uio_num: usize, mode: LockMode) -> Result<Self> {
+        DeviceLock::lock(sysfs, uio_num, |file| match mode {
+            LockMode::Blocking => FileExt::lock_exclusive(file),
+            LockMode::Try => FileExt::try_lock_exclusive(file),
+        })
+    }
+
+    /// Lock the device node `uioN` shared with other read-only users.
+    ///
+    /// Fails with `Error::Busy` if another process holds an exclusive lock.
+    pub fn acquire_shared(sysfs: &Sysfs, uio_num: usize) -> Result<Self> {
+        DeviceLock::lock(sysfs, uio_num, FileExt::try_lock_shared)
+    }
+
+    fn lock<F>(sysfs: &Sysfs, uio_num: usize, lock: F) -> Result<Self>
+        where F: FnOnce(&File) -> io::Result<()>
+    {
+        let file = File::open(sysfs.dev_path(uio_num)).map_err(|err| Error::Open(uio_num, err))?;
+        match lock(&file) {
+            Ok(()) => Ok(DeviceLock { _file: file }),
+            Err(ref err) if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() =>
+                Err(Error::Busy(uio_num, holder(&file))),
+            Err(err) => Err(Error::Lock(uio_num, err)),
+        }
+    }
+}
+
+/// Read-only handle to a device opened with a shared lock.
+///
+/// Only gives access to the `&self` methods of the device, so registers
+/// can be read but not written while other processes use the device.
+pub struct Shared<D> {
+    device: D,
+}
+
+impl<D> Shared<D> {
+    pub fn new(device: D) -> Self {
+        Shared { device }
+    }
+}
+
+impl<D> Deref for Shared<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        &self.device
+    }
+}
+
+/// Find the pid of a process holding a lock on `file` in `/proc/locks`.
+///
+/// Lines of processes waiting for a lock are marked with `->` and skipped.
+fn holder(file: &File) -> Option<u32> {
+    let metadata = file.metadata().ok()?;
+    let dev = metadata.dev();
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let id = format!("{:02x}:{:02x}:{}", major, minor, metadata.ino());
+    let locks = fs::read_to_string("/proc/locks").ok()?;
+    parse_holder(&locks, &id)
+}
+
+/// Find the pid holding a lock on the inode `id` in the contents of `/proc/locks`.
+fn parse_holder(locks: &str, id: &str) -> Option<u32> {
+    locks.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| fields.get(1) != Some(&"->"))
+        .find(|fields| fields.len() > 5 && fields[5] == id)
+        .and_then(|fields| fields[4].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_holder() {
+        let locks = "1: FLOCK  ADVISORY  WRITE 1234 00:05:321 0 EOF\n\
+                     1: -> FLOCK  ADVISORY  WRITE 5678 00:05:123 0 EOF\n\
+                     2: FLOCK  ADVISORY  READ  4321 00:05:123 0 EOF\n\
+                     2: -> FLOCK  ADVISORY  WRITE 8765 00:05:123 0 EOF\n";
+        assert_eq!(parse_holder(locks, "00:05:123"), Some(4321));
+        assert_eq!(parse_holder(locks, "00:05:321"), Some(1234));
+        assert_eq!(parse_holder(locks, "00:05:999"), None);
+    }
+}
@@ -1,3 +1,7 @@
+use compat::{self, Compat};
+use error::Result;
+use lock::{DeviceLock, LockMode, Shared};
+use sysfs::Sysfs;
 use uio::*;
 use volatile_register::RW;
 
@@ -9,16 +13,127 @@ pub struct MgmtRegs {
     cfg_loop: RW<u32>,
 }
 
+/// Pin of the extension connector E1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GpioPin {
+    P0 = 0, P1 = 1, P2 = 2, P3 = 3, P4 = 4, P5 = 5, P6 = 6, P7 = 7,
+    N0 = 8, N1 = 9, N2 = 10, N3 = 11, N4 = 12, N5 = 13, N6 = 14, N7 = 15,
+}
+
+pub const GPIO_PINS: [GpioPin; 16] = [
+    GpioPin::P0, GpioPin::P1, GpioPin::P2, GpioPin::P3,
+    GpioPin::P4, GpioPin::P5, GpioPin::P6, GpioPin::P7,
+    GpioPin::N0, GpioPin::N1, GpioPin::N2, GpioPin::N3,
+    GpioPin::N4, GpioPin::N5, GpioPin::N6, GpioPin::N7,
+];
+
+impl GpioPin {
+    /// Bit of the pin in `cfg_iom`, {exp_n_io[7:0], exp_p_io[7:0]}.
+    pub fn mask(&self) -> u32 {
+        1 << *self as u32
+    }
+}
+
+/// Controller driving a GPIO pin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GpioRoute {
+    /// PS GPIO controller.
+    Ps,
+    /// Logic generator.
+    LogicGenerator,
+}
+
+/// Routes of several GPIO pins, applied in one read-modify-write.
+///
+/// Pins without a route keep their current one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct GpioConfig {
+    mask: u32,
+    mode: u32,
+}
+
+impl GpioConfig {
+    pub fn new() -> Self {
+        GpioConfig { mask: 0, mode: 0 }
+    }
+
+    /// Route `pin` to `route`.
+    pub fn pin(mut self, pin: GpioPin, route: GpioRoute) -> Self {
+        self.mask |= pin.mask();
+        match route {
+            GpioRoute::Ps => self.mode &= !pin.mask(),
+            GpioRoute::LogicGenerator => self.mode |= pin.mask(),
+        }
+        self
+    }
+
+    /// Route all `pins` to `route`.
+    pub fn pins(self, pins: &[GpioPin], route: GpioRoute) -> Self {
+        pins.iter().fold(self, |config, pin| config.pin(*pin, route))
+    }
+
+    /// Apply the routes to a GPIO mode.
+    pub fn apply_to(&self, mode: u32) -> u32 {
+        (mode & !self.mask) | self.mode
+    }
+}
+
 pub struct Management {
-    regs: MemoryMap,
+    regs: Box<dyn Mapping>,
+    _lock: Option<DeviceLock>,
+    compat: Compat,
 }
 
 impl Management {
-    pub fn new() -> Self {
-        let uio_num = get_uio_num("/dev/uio/mgmt");
-        let uio = UioDevice::new(uio_num).unwrap();
-        let regs = uio.map_mapping(0).unwrap();
-        Management { regs }
+    /// Open the management device, failing if another process is using it.
+    pub fn new() -> Result<Self> {
+        Management::with_lock(LockMode::Try)
+    }
+
+    /// Open the management device, locking it against other processes with `mode`.
+    pub fn with_lock(mode: LockMode) -> Result<Self> {
+        Management::with_sysfs(&Sysfs::new(), mode)
+    }
+
+    /// Open the management device found through `sysfs`, locking it with `mode`.
+    pub fn with_sysfs(sysfs: &Sysfs, mode: LockMode) -> Result<Self> {
+        Management::open(sysfs, |uio_num| DeviceLock::acquire(sysfs, uio_num, mode))
+    }
+
+    /// Open the management device read-only, sharing it with other read-only users.
+    ///
+    /// Fails with `Error::Busy` if another process holds an exclusive lock.
+    pub fn shared() -> Result<Shared<Self>> {
+        Management::shared_with_sysfs(&Sysfs::new())
+    }
+
+    /// Open the management device found through `sysfs` read-only, see `shared`.
+    pub fn shared_with_sysfs(sysfs: &Sysfs) -> Result<Shared<Self>> {
+        Management::open(sysfs, |uio_num| DeviceLock::acquire_shared(sysfs, uio_num))
+            .map(Shared::new)
+    }
+
+    fn open<F: FnOnce(usize) -> Result<DeviceLock>>(sysfs: &Sysfs, lock: F) -> Result<Self> {
+        let compat = compat::check(sysfs)?;
+        let uio = open_uio(sysfs, "mgmt")?;
+        let lock = lock(uio.num)?;
+        let regs = map_mapping(sysfs, &uio, 0)?;
+        let mut mgmt = Management::from_mapping(regs)?;
+        mgmt._lock = Some(lock);
+        mgmt.compat = compat;
+        Ok(mgmt)
+    }
+
+    /// Create a management device backed by arbitrary register memory.
+    pub fn from_mapping(regs: Box<dyn Mapping>) -> Result<Self> {
+        check_size::<MgmtRegs>(&*regs)?;
+        Ok(Management { regs, _lock: None, compat: Compat::Unchecked })
+    }
+
+    /// Outcome of the compatibility check of the bitstream when the device
+    /// was opened, `Compat::Unchecked` for devices created from mappings.
+    pub fn compat(&self) -> Compat {
+        self.compat
     }
 
     /// Get GPIO mode.
@@ -35,6 +150,26 @@ impl Management {
         unsafe { self.mut_regs().cfg_iom.write(mode); }
     }
 
+    /// Get the controller driving `pin`.
+    pub fn gpio_route(&self, pin: GpioPin) -> GpioRoute {
+        if self.gpio_mode() & pin.mask() == 0 {
+            GpioRoute::Ps
+        } else {
+            GpioRoute::LogicGenerator
+        }
+    }
+
+    /// Set the controller driving `pin`, leaving the other pins unchanged.
+    pub fn set_gpio_route(&mut self, pin: GpioPin, route: GpioRoute) {
+        self.configure_gpio(&GpioConfig::new().pin(pin, route));
+    }
+
+    /// Set the routes of several pins, leaving the other pins unchanged.
+    pub fn configure_gpio(&mut self, config: &GpioConfig) {
+        let mode = config.apply_to(self.gpio_mode());
+        self.set_gpio_mode(mode);
+    }
+
     /// Get digital loopback register (for debugging purposes).
     ///
     /// Each bit controls one of the loop paths:
@@ -50,11 +185,11 @@ impl Management {
     }
 }
 
-impl RegDevice for Management {
+unsafe impl RegDevice for Management {
     type Registers = MgmtRegs;
 
-    fn reg_mmap(&self) -> &MemoryMap {
-        &self.regs
+    fn reg_mmap(&self) -> &dyn Mapping {
+        &*self.regs
     }
 }
 
@@ -73,3 +208,32 @@ impl Show for Management {
         println!("cfg_loop = {:x}", self.regs().cfg_loop.read());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fake::{self, FakeMemory};
+    use super::*;
+
+    #[test]
+    fn gpio_routes() {
+        let mut mgmt = Management::from_mapping(Box::new(FakeMemory::new(8))).unwrap();
+        mgmt.set_gpio_mode(0x8001);
+        mgmt.set_gpio_route(GpioPin::N0, GpioRoute::LogicGenerator);
+        assert_eq!(fake::read(mgmt.reg_mmap(), 0x0), 0x8101);
+        mgmt.configure_gpio(&GpioConfig::new()
+                            .pin(GpioPin::P0, GpioRoute::Ps)
+                            .pins(&[GpioPin::P7, GpioPin::N6], GpioRoute::LogicGenerator));
+        assert_eq!(mgmt.gpio_mode(), 0xc180);
+        assert_eq!(mgmt.gpio_route(GpioPin::N7), GpioRoute::LogicGenerator);
+        assert_eq!(mgmt.gpio_route(GpioPin::P1), GpioRoute::Ps);
+    }
+
+    #[test]
+    fn gen_osc_loop() {
+        let mut mgmt = Management::from_mapping(Box::new(FakeMemory::new(8))).unwrap();
+        mgmt.set_gen_osc_loop(0x2);
+        assert_eq!(fake::read(mgmt.reg_mmap(), 0x4), 0x2);
+        mgmt.default();
+        assert_eq!(mgmt.gen_osc_loop(), 0);
+    }
+}
@@ -0,0 +1,413 @@
+use acquire::{self, *};
+use board::{BoardFamily, BoardInfo, Module};
+use compat::{self, Compat};
+use error::Result;
+use event::*;
+use lock::{DeviceLock, LockMode, Shared};
+use osc_filter::*;
+use osc_trigger::*;
+use sysfs::Sysfs;
+use uio::*;
+use volatile_register::RO;
+
+pub const BUFFER_SIZE: u32 = 0x4000; // 2**14 = 16384
+/// Sampling frequency of the STEMlab 125-14, see `BoardInfo`.
+pub const SAMPLING_FREQUENCY: u32 = 125_000_000;
+/// ADC resolution of the STEMlab 125-14 in bits, see `BoardInfo`.
+pub const ADC_BITS: u32 = 14;
+
+#[repr(C)]
+pub struct OscRegs {
+    event: EventRegs,
+    _rsv0: u32,
+    acquire: AcquireRegs,
+    trigger: OscTriggerRegs,
+    filter: OscFilterRegs,
+}
+
+impl Default for OscRegs {
+    /// Set registers to default (power-up) state.
+    fn default(&mut self) {
+        self.event.default();
+        self.acquire.default();
+        self.trigger.default();
+        self.filter.default();
+    }
+}
+
+impl Show for OscRegs {
+    /// Print FPGA module registers.
+    fn show(&self) {
+        self.event.show();
+        self.acquire.show();
+        self.trigger.show();
+        self.filter.show();
+    }
+}
+
+pub struct Oscilloscope {
+    regs: Box<dyn Mapping>,
+    buffer: Box<dyn Mapping>,
+    full_scale: f32,
+    board: BoardInfo,
+    _lock: Option<DeviceLock>,
+    compat: Compat,
+}
+
+impl Oscilloscope {
+    /// Open oscilloscope channel `channel` (`0` or `1`), failing if another process is using it.
+    ///
+    /// Fails if the loaded bitstream has no oscilloscope for `channel`.
+    pub fn new(channel: usize) -> Result<Self> {
+        Oscilloscope::with_lock(channel, LockMode::Try)
+    }
+
+    /// Open oscilloscope channel `channel`, locking it against other processes with `mode`.
+    pub fn with_lock(channel: usize, mode: LockMode) -> Result<Self> {
+        Oscilloscope::with_sysfs(&Sysfs::new(), channel, mode)
+    }
+
+    /// Open channel `channel` of the device found through `sysfs`, locking it with `mode`.
+    pub fn with_sysfs(sysfs: &Sysfs, channel: usize, mode: LockMode) -> Result<Self> {
+        Oscilloscope::open(sysfs, channel, |uio_num| DeviceLock::acquire(sysfs, uio_num, mode))
+    }
+
+    /// Open oscilloscope channel `channel` read-only, sharing it with other read-only users.
+    ///
+    /// Fails with `Error::Busy` if another process holds an exclusive lock.
+    pub fn shared(channel: usize) -> Result<Shared<Self>> {
+        Oscilloscope::shared_with_sysfs(&Sysfs::new(), channel)
+    }
+
+    /// Open channel `channel` of the device found through `sysfs` read-only, see `shared`.
+    pub fn shared_with_sysfs(sysfs: &Sysfs, channel: usize) -> Result<Shared<Self>> {
+        Oscilloscope::open(sysfs, channel, |uio_num| DeviceLock::acquire_shared(sysfs, uio_num))
+            .map(Shared::new)
+    }
+
+    fn open<F>(sysfs: &Sysfs, channel: usize, lock: F) -> Result<Self>
+        where F: FnOnce(usize) -> Result<DeviceLock>
+    {
+        let compat = compat::check(sysfs)?;
+        let board = BoardInfo::detect(sysfs)?;
+        if let Some(module) = Module::osc(channel) {
+            board.require(module)?;
+        }
+        let uio = open_uio(sysfs, &format!("osc{}", channel))?;
+        let lock = lock(uio.num)?;
+        let regs = map_mapping(sysfs, &uio, 0)?;
+        let buffer = map_mapping(sysfs, &uio, 1)?;
+        let mut osc = Oscilloscope::from_mappings(regs, buffer)?;
+        osc.set_board(board);
+        osc._lock = Some(lock);
+        osc.compat = compat;
+        Ok(osc)
+    }
+
+    /// Create an oscilloscope backed by arbitrary register and buffer memory.
+    ///
+    /// Assumes a STEMlab 125-14, see `set_board`.
+    pub fn from_mappings(regs: Box<dyn Mapping>, buffer: Box<dyn Mapping>) -> Result<Self> {
+        check_size::<OscRegs>(&*regs)?;
+        check_size::<<Self as BufferDevice>::Buffer>(&*buffer)?;
+        let board = BoardInfo::with_family(BoardFamily::Stemlab125_14);
+        Ok(Oscilloscope {
+            regs, buffer, full_scale: 1.0, board, _lock: None, compat: Compat::Unchecked,
+        })
+    }
+
+    /// Outcome of the compatibility check of the bitstream when the device
+    /// was opened, `Compat::Unchecked` for devices created from mappings.
+    pub fn compat(&self) -> Compat {
+        self.compat
+    }
+
+    /// Board the sample width and rate are taken from.
+    pub fn board(&self) -> BoardInfo {
+        self.board
+    }
+
+    /// Set the board the sample width and rate are taken from.
+    pub fn set_board(&mut self, board: BoardInfo) {
+        self.board = board;
+    }
+
+    /// Get input full scale range in volts.
+    pub fn full_scale(&self) -> f32 {
+        self.full_scale
+    }
+
+    /// Set input full scale range in volts.
+    ///
+    /// Depends on the input jumper setting (1V for LV, 20V for HV).
+    pub fn set_full_scale(&mut self, full_scale: f32) {
+        self.full_scale = full_scale;
+    }
+
+    /// Convert a raw sample to volts.
+    pub fn to_volts(&self, sample: i16) -> f32 {
+        sample as f32 * self.full_scale / (1 << (self.board.adc_bits() - 1)) as f32
+    }
+
+    /// Convert volts to a raw sample.
+    pub fn volts_to_raw(&self, volts: f32) -> i32 {
+        let bits = self.board.adc_bits();
+        let max = (1 << (bits - 1)) - 1;
+        let min = -(1 << (bits - 1));
+        let raw = (volts / self.full_scale * (1 << (bits - 1)) as f32).round() as i32;
+        if raw > max { max } else if raw < min { min } else { raw }
+    }
+
+    /// Set level trigger in volts.
+    ///
+    /// The trigger fires when the signal crosses `level` in the direction
+    /// of the selected edge, after having been at least `hysteresis` volts
+    /// on the other side of it.
+    pub fn set_trigger_level_volts(&mut self, level: f32, hysteresis: f32) {
+        let (neg, pos) = match self.trigger_edge() {
+            Edge::Positive => (level - hysteresis, level),
+            Edge::Negative => (level, level + hysteresis),
+        };
+        let (neg, pos) = (self.volts_to_raw(neg), self.volts_to_raw(pos));
+        self.set_trigger_level(neg, pos);
+    }
+
+    /// Returns the sample rate depending on decimation factor.
+    pub fn sample_rate(&self) -> f32 {
+        self.board.sampling_frequency() as f32 / self.decimation() as f32
+    }
+
+    /// Returns the sample period depending on decimation factor.
+    pub fn sample_period(&self) -> f32 {
+        1.0 / self.sample_rate()
+    }
+
+    /// Mask out overflow bit and sum pre and post trigger counters.
+    ///
+    /// Returns the position in the buffer the next sample will be written to.
+    pub fn pointer(&self) -> usize {
+        acquire::pointer(self, BUFFER_SIZE as usize)
+    }
+
+    /// Number of valid samples stored in the buffer.
+    pub fn available(&self) -> usize {
+        acquire::available(self, BUFFER_SIZE as usize)
+    }
+
+    /// Read data buffer.
+    ///
+    /// Returns the last `num_samples` samples, at most `available()`, sign
+    /// extended from the ADC resolution of the board in chronological order
+    /// (oldest first).
+    pub fn data(&self, num_samples: usize) -> Vec<i16> {
+        let buffer = self.buffer();
+        let shift = 16 - self.board.adc_bits();
+        acquire::indices(self, BUFFER_SIZE as usize, num_samples)
+            .map(|i| (buffer[i].read() << shift) >> shift)
+            .collect()
+    }
+
+    /// Read data buffer and convert the samples to volts.
+    pub fn volts(&self, num_samples: usize) -> Vec<f32> {
+        self.data(num_samples).into_iter().map(|s| self.to_volts(s)).collect()
+    }
+
+    /// Position of the trigger sample in the array returned by `data`.
+    pub fn trigger_position(&self, num_samples: usize) -> Option<usize> {
+        acquire::trigger_position(self, self.is_triggered(), BUFFER_SIZE as usize, num_samples)
+    }
+}
+
+unsafe impl RegDevice for Oscilloscope {
+    type Registers = OscRegs;
+
+    fn reg_mmap(&self) -> &dyn Mapping {
+        &*self.regs
+    }
+}
+
+unsafe impl BufferDevice for Oscilloscope {
+    type Buffer = [RO<i16>; BUFFER_SIZE as usize];
+
+    fn buffer_mmap(&self) -> &dyn Mapping {
+        &*self.buffer
+    }
+}
+
+impl Default for Oscilloscope {
+    fn default(&mut self) {
+        self.mut_regs().default();
+    }
+}
+
+impl Show for Oscilloscope {
+    fn show(&self) {
+        self.regs().show();
+    }
+}
+
+impl EventRegsAPI for Oscilloscope {
+    fn reset(&mut self) {
+        self.mut_regs().event.reset();
+    }
+    fn start(&mut self) {
+        self.mut_regs().event.start();
+    }
+    fn stop(&mut self) {
+        self.mut_regs().event.stop();
+    }
+    fn trigger(&mut self) {
+        self.mut_regs().event.trigger();
+    }
+    fn start_trigger(&mut self) {
+        self.mut_regs().event.start_trigger();
+    }
+    fn is_running(&self) -> bool {
+        self.regs().event.is_running()
+    }
+    fn is_triggered(&self) -> bool {
+        self.regs().event.is_triggered()
+    }
+    fn sync_source(&self) -> Result<SyncSource> {
+        self.regs().event.sync_source()
+    }
+    fn set_sync_source(&mut self, event: SyncSource) {
+        self.mut_regs().event.set_sync_source(event);
+    }
+    fn trigger_source(&self) -> TriggerSources {
+        self.regs().event.trigger_source()
+    }
+    fn set_trigger_source<T: Into<TriggerSources>>(&mut self, trigger: T) {
+        self.mut_regs().event.set_trigger_source(trigger);
+    }
+}
+
+impl AcquireRegsAPI for Oscilloscope {
+    fn trigger_pre(&self) -> u32 {
+        self.regs().acquire.trigger_pre()
+    }
+    fn set_trigger_pre(&mut self, delay: u32) {
+        self.mut_regs().acquire.set_trigger_pre(delay);
+    }
+    fn trigger_post(&self) -> u32 {
+        self.regs().acquire.trigger_post()
+    }
+    fn set_trigger_post(&mut self, delay: u32) {
+        self.mut_regs().acquire.set_trigger_post(delay);
+    }
+    fn trigger_pre_status(&self) -> u32 {
+        self.regs().acquire.trigger_pre_status()
+    }
+    fn trigger_post_status(&self) -> u32 {
+        self.regs().acquire.trigger_post_status()
+    }
+}
+
+impl OscTriggerRegsAPI for Oscilloscope {
+    fn trigger_level(&self) -> (i32, i32) {
+        self.regs().trigger.trigger_level()
+    }
+    fn set_trigger_level(&mut self, neg: i32, pos: i32) {
+        self.mut_regs().trigger.set_trigger_level(neg, pos);
+    }
+    fn trigger_edge(&self) -> Edge {
+        self.regs().trigger.trigger_edge()
+    }
+    fn set_trigger_edge(&mut self, edge: Edge) {
+        self.mut_regs().trigger.set_trigger_edge(edge);
+    }
+    fn trigger_holdoff(&self) -> u32 {
+        self.regs().trigger.trigger_holdoff()
+    }
+    fn set_trigger_holdoff(&mut self, holdoff: u32) {
+        self.mut_regs().trigger.set_trigger_holdoff(holdoff);
+    }
+}
+
+impl OscFilterRegsAPI for Oscilloscope {
+    fn decimation(&self) -> u32 {
+        self.regs().filter.decimation()
+    }
+    fn set_decimation(&mut self, dec: u32) {
+        self.mut_regs().filter.set_decimation(dec);
+    }
+    fn average(&self) -> bool {
+        self.regs().filter.average()
+    }
+    fn set_average(&mut self, avg: bool) {
+        self.mut_regs().filter.set_average(avg);
+    }
+    fn filter_bypass(&self) -> bool {
+        self.regs().filter.filter_bypass()
+    }
+    fn set_filter_bypass(&mut self, bypass: bool) {
+        self.mut_regs().filter.set_filter_bypass(bypass);
+    }
+    fn filter_coefficients(&self) -> FilterCoefficients {
+        self.regs().filter.filter_coefficients()
+    }
+    fn set_filter_coefficients(&mut self, coeff: FilterCoefficients) {
+        self.mut_regs().filter.set_filter_coefficients(coeff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error::Error;
+    use fake::{self, FakeMemory, FakeSysfs};
+    use super::*;
+
+    fn oscilloscope() -> Oscilloscope {
+        Oscilloscope::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                    Box::new(FakeMemory::new(BUFFER_SIZE as usize * 2))).unwrap()
+    }
+
+    #[test]
+    fn data_sign_extended() {
+        let osc = oscilloscope();
+        fake::write_u16(osc.buffer_mmap(), 0, 0x3fff);
+        fake::write_u16(osc.buffer_mmap(), 2, 0x1fff);
+        fake::write(osc.reg_mmap(), 0x18, 2);
+        assert_eq!(osc.data(4), vec![-1, 8191]);
+    }
+
+    #[test]
+    fn decimation() {
+        let mut osc = oscilloscope();
+        osc.set_decimation(8);
+        assert_eq!(fake::read(osc.reg_mmap(), 0x30), 7);
+        assert_eq!(fake::read(osc.reg_mmap(), 0x34), 3);
+        osc.set_decimation(0);
+        assert_eq!(fake::read(osc.reg_mmap(), 0x30), 0);
+        assert_eq!(fake::read(osc.reg_mmap(), 0x34), 0);
+        assert_eq!(osc.decimation(), 1);
+    }
+
+    #[test]
+    fn trigger_level() {
+        let mut osc = oscilloscope();
+        osc.set_trigger_level_volts(0.5, 0.1);
+        assert_eq!(osc.trigger_level(), (osc.volts_to_raw(0.4), 4096));
+        assert!((osc.to_volts(4096) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lock() {
+        let fake = FakeSysfs::new();
+        fake.add_mercury_devices(fake::HWID);
+        let sysfs = fake.sysfs();
+        let osc = Oscilloscope::with_sysfs(&sysfs, 0, LockMode::Try).unwrap();
+        match Oscilloscope::with_sysfs(&sysfs, 0, LockMode::Try) {
+            Err(Error::Busy(..)) => {}
+            _ => panic!("second exclusive open should fail"),
+        }
+        assert!(Oscilloscope::shared_with_sysfs(&sysfs, 0).is_err());
+        // The other channel is a separate device.
+        Oscilloscope::with_sysfs(&sysfs, 1, LockMode::Try).unwrap();
+        drop(osc);
+        let first = Oscilloscope::shared_with_sysfs(&sysfs, 0).unwrap();
+        let second = Oscilloscope::shared_with_sysfs(&sysfs, 0).unwrap();
+        assert_eq!(first.decimation(), second.decimation());
+        assert!(Oscilloscope::with_sysfs(&sysfs, 0, LockMode::Try).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::cmp;
+use uio::*;
+use volatile_register::RW;
+
+/// Equalization filter coefficients.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FilterCoefficients {
+    pub aa: i32,
+    pub bb: i32,
+    pub kk: i32,
+    pub pp: i32,
+}
+
+#[repr(C)]
+pub struct OscFilterRegs {
+    /// Decimation factor
+    cfg_dec: RW<u32>,
+    /// Shift right
+    cfg_shr: RW<u32>,
+    /// Average enable
+    cfg_avg: RW<u32>,
+    /// Filter bypass
+    cfg_byp: RW<u32>,
+    /// AA coefficient
+    cfg_faa: RW<i32>,
+    /// BB coefficient
+    cfg_fbb: RW<i32>,
+    /// KK coefficient
+    cfg_fkk: RW<i32>,
+    /// PP coefficient
+    cfg_fpp: RW<i32>,
+}
+
+impl Default for OscFilterRegs {
+    fn default(&mut self) {
+        unsafe {
+            self.cfg_dec.write(0);
+            self.cfg_shr.write(0);
+            self.cfg_avg.write(0);
+            self.cfg_byp.write(1);
+            self.cfg_faa.write(0);
+            self.cfg_fbb.write(0);
+            self.cfg_fkk.write(0xffffff);
+            self.cfg_fpp.write(0);
+        }
+    }
+}
+
+impl Show for OscFilterRegs {
+    fn show(&self) {
+        println!("cfg_dec = {:x}", self.cfg_dec.read());
+        println!("cfg_shr = {:x}", self.cfg_shr.read());
+        println!("cfg_avg = {:x}", self.cfg_avg.read());
+        println!("cfg_byp = {:x}", self.cfg_byp.read());
+        println!("cfg_faa = {:x}", self.cfg_faa.read());
+        println!("cfg_fbb = {:x}", self.cfg_fbb.read());
+        println!("cfg_fkk = {:x}", self.cfg_fkk.read());
+        println!("cfg_fpp = {:x}", self.cfg_fpp.read());
+    }
+}
+
+pub trait OscFilterRegsAPI {
+    /// Get decimation factor.
+    fn decimation(&self) -> u32;
+    /// Set decimation factor.
+    ///
+    /// Also sets the shift used when averaging, so the sum of `dec`
+    /// samples is scaled back to the sample range. A factor of 0 is
+    /// treated as 1, no decimation.
+    fn set_decimation(&mut self, dec: u32);
+    /// Get averaging mode.
+    fn average(&self) -> bool;
+    /// Set averaging mode.
+    ///
+    /// When enabled the decimator outputs the average over all samples
+    /// instead of dropping all but every `dec` sample.
+    fn set_average(&mut self, avg: bool);
+    /// Get filter bypass.
+    fn filter_bypass(&self) -> bool;
+    /// Set filter bypass.
+    fn set_filter_bypass(&mut self, bypass: bool);
+    /// Get equalization filter coefficients.
+    fn filter_coefficients(&self) -> FilterCoefficients;
+    /// Set equalization filter coefficients.
+    fn set_filter_coefficients(&mut self, coeff: FilterCoefficients);
+}
+
+/// Number of bits the sum of `dec` samples needs to be shifted right.
+fn shift(dec: u32) -> u32 {
+    32 - (dec - 1).leading_zeros()
+}
+
+impl OscFilterRegsAPI for OscFilterRegs {
+    fn decimation(&self) -> u32 {
+        self.cfg_dec.read() + 1
+    }
+    fn set_decimation(&mut self, dec: u32) {
+        let dec = cmp::max(dec, 1);
+        unsafe {
+            self.cfg_dec.write(dec - 1);
+            self.cfg_shr.write(shift(dec));
+        }
+    }
+    fn average(&self) -> bool {
+        self.cfg_avg.read() > 0
+    }
+    fn set_average(&mut self, avg: bool) {
+        unsafe { self.cfg_avg.write(avg as u32); }
+    }
+    fn filter_bypass(&self) -> bool {
+        self.cfg_byp.read() > 0
+    }
+    fn set_filter_bypass(&mut self, bypass: bool) {
+        unsafe { self.cfg_byp.write(bypass as u32); }
+    }
+    fn filter_coefficients(&self) -> FilterCoefficients {
+        FilterCoefficients {
+            aa: self.cfg_faa.read(),
+            bb: self.cfg_fbb.read(),
+            kk: self.cfg_fkk.read(),
+            pp: self.cfg_fpp.read(),
+        }
+    }
+    fn set_filter_coefficients(&mut self, coeff: FilterCoefficients) {
+        unsafe {
+            self.cfg_faa.write(coeff.aa);
+            self.cfg_fbb.write(coeff.bb);
+            self.cfg_fkk.write(coeff.kk);
+            self.cfg_fpp.write(coeff.pp);
+        }
+    }
+}
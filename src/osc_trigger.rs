@@ -0,0 +1,89 @@
+use uio::*;
+use volatile_register::RW;
+
+/// Edge the level trigger reacts to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edge {
+    Positive = 0,
+    Negative = 1,
+}
+
+#[repr(C)]
+pub struct OscTriggerRegs {
+    /// Positive level
+    cfg_pos: RW<i32>,
+    /// Negative level
+    cfg_neg: RW<i32>,
+    /// Edge (0 - positive, 1 - negative)
+    cfg_edg: RW<u32>,
+    /// Hold off
+    cfg_hld: RW<u32>,
+}
+
+impl Default for OscTriggerRegs {
+    fn default(&mut self) {
+        unsafe {
+            self.cfg_pos.write(0);
+            self.cfg_neg.write(0);
+            self.cfg_edg.write(0);
+            self.cfg_hld.write(0);
+        }
+    }
+}
+
+impl Show for OscTriggerRegs {
+    fn show(&self) {
+        println!("cfg_pos = {:x}", self.cfg_pos.read());
+        println!("cfg_neg = {:x}", self.cfg_neg.read());
+        println!("cfg_edg = {:x}", self.cfg_edg.read());
+        println!("cfg_hld = {:x}", self.cfg_hld.read());
+    }
+}
+
+pub trait OscTriggerRegsAPI {
+    /// Get trigger levels `(negative, positive)` in raw ADC counts.
+    ///
+    /// The trigger fires when the signal crosses both levels in the
+    /// direction of the selected edge, the difference between the two
+    /// levels is the hysteresis.
+    fn trigger_level(&self) -> (i32, i32);
+    /// Set trigger levels `(negative, positive)` in raw ADC counts.
+    fn set_trigger_level(&mut self, neg: i32, pos: i32);
+    /// Get trigger edge.
+    fn trigger_edge(&self) -> Edge;
+    /// Set trigger edge.
+    fn set_trigger_edge(&mut self, edge: Edge);
+    /// Get trigger hold off.
+    ///
+    /// Number of samples after a trigger during which new triggers are ignored.
+    fn trigger_holdoff(&self) -> u32;
+    /// Set trigger hold off.
+    fn set_trigger_holdoff(&mut self, holdoff: u32);
+}
+
+impl OscTriggerRegsAPI for OscTriggerRegs {
+    fn trigger_level(&self) -> (i32, i32) {
+        (self.cfg_neg.read(), self.cfg_pos.read())
+    }
+    fn set_trigger_level(&mut self, neg: i32, pos: i32) {
+        unsafe {
+            self.cfg_neg.write(neg);
+            self.cfg_pos.write(pos);
+        }
+    }
+    fn trigger_edge(&self) -> Edge {
+        match self.cfg_edg.read() & 1 {
+            0 => Edge::Positive,
+            _ => Edge::Negative,
+        }
+    }
+    fn set_trigger_edge(&mut self, edge: Edge) {
+        unsafe { self.cfg_edg.write(edge as u32); }
+    }
+    fn trigger_holdoff(&self) -> u32 {
+        self.cfg_hld.read()
+    }
+    fn set_trigger_holdoff(&mut self, holdoff: u32) {
+        unsafe { self.cfg_hld.write(holdoff); }
+    }
+}
@@ -1,10 +1,24 @@
 pub use acquire::AcquireRegsAPI;
-pub use event::{EventRegsAPI, SyncSource, TriggerSource};
+pub use board::{BoardFamily, BoardInfo, Module};
+pub use burst::BurstRegsAPI;
+pub use error::Error;
+pub use event::{EventRegsAPI, SyncSource, TriggerSource, TriggerSources};
+pub use firmware::Firmware;
+pub use gen::Generator;
+pub use gen_linear::GenLinearRegsAPI;
+pub use gen_table::GenTableRegsAPI;
 pub use hwid::HwId;
 pub use interrupts::Interrupt;
 pub use la::LogicAnalyzer;
+pub use la_decode::Decoder;
 pub use la_mask::LaMaskRegsAPI;
 pub use la_rle::LaRleRegsAPI;
 pub use la_trigger::LaTriggerRegsAPI;
-pub use management::Management;
-pub use uio::{Default, Show, RegDevice, BufferDevice, IrqAPI};
+pub use lg::LogicGenerator;
+pub use lg_output::LgOutputRegsAPI;
+pub use lock::{LockMode, Shared};
+pub use management::{GpioConfig, GpioPin, GpioRoute, Management};
+pub use osc::Oscilloscope;
+pub use osc_filter::{FilterCoefficients, OscFilterRegsAPI};
+pub use osc_trigger::{Edge, OscTriggerRegsAPI};
+pub use uio::{Default, Show, Mapping, RegDevice, BufferDevice, IrqAPI};
@@ -0,0 +1,296 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+use acquire::AcquireRegsAPI;
+use error::{Error, Result};
+use event::EventRegsAPI;
+use gen::Generator;
+use management::Management;
+use osc::Oscilloscope;
+use osc_filter::OscFilterRegsAPI;
+use uio::Default;
+
+/// Number of samples in one period of the test waveform.
+const PERIOD: usize = 1024;
+/// Amplitude of the test waveform relative to full scale.
+const AMPLITUDE: f32 = 0.9;
+
+/// Result of testing one gen -> osc loop.
+#[derive(Debug)]
+pub struct LoopReport {
+    /// Channel of the generator and oscilloscope.
+    pub channel: usize,
+    /// Largest deviation of the captured from the generated waveform,
+    /// relative to full scale, or `None` if nothing was captured.
+    pub max_error: Option<f32>,
+    /// Error that prevented capturing the waveform.
+    pub error: Option<Error>,
+    /// Whether the deviation is within tolerance.
+    pub passed: bool,
+}
+
+/// Result of a self test, one entry per loop.
+#[derive(Debug)]
+pub struct Report {
+    pub loops: Vec<LoopReport>,
+}
+
+impl Report {
+    /// Whether all loops passed.
+    pub fn passed(&self) -> bool {
+        self.loops.iter().all(|report| report.passed)
+    }
+}
+
+impl fmt::Display for LoopReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = if self.passed { "pass" } else { "FAIL" };
+        write!(f, "gen{} -> osc{}: {}", self.channel, self.channel, result)?;
+        match (self.max_error, &self.error) {
+            (_, Some(err)) => write!(f, " ({})", err),
+            (Some(max_error), None) => write!(f, " (max error {:.4})", max_error),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, report) in self.loops.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", report)?;
+        }
+        Ok(())
+    }
+}
+
+/// Self test using the digital gen -> osc loops of the management module.
+///
+/// Each generator outputs a sine wave which its oscilloscope captures
+/// through the loop, the capture has to match the table within tolerance.
+pub struct SelfTest {
+    tolerance: f32,
+    num_samples: usize,
+    timeout: Duration,
+}
+
+impl SelfTest {
+    pub fn new() -> Self {
+        SelfTest {
+            tolerance: 0.01,
+            num_samples: 2 * PERIOD,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    /// Set the allowed deviation relative to full scale.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance;
+    }
+
+    /// Set how long to wait for the oscilloscope to fill its buffer.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Test both loops, opening the generators and oscilloscopes.
+    pub fn run(&self, mgmt: &mut Management) -> Result<Report> {
+        let mut loops = Vec::new();
+        for channel in 0..2 {
+            let mut gen = Generator::new(channel)?;
+            let mut osc = Oscilloscope::new(channel)?;
+            loops.push(self.test_loop(mgmt, &mut gen, &mut osc, channel));
+        }
+        Ok(Report { loops })
+    }
+
+    /// Test the loop from `gen` to `osc` of `channel`.
+    ///
+    /// The loop is only enabled for the duration of the test.
+    pub fn test_loop(&self, mgmt: &mut Management, gen: &mut Generator,
+                     osc: &mut Oscilloscope, channel: usize) -> LoopReport {
+        let loops = mgmt.gen_osc_loop();
+        mgmt.set_gen_osc_loop(loops | 1 << channel);
+        let result = self.capture(gen, osc);
+        gen.stop();
+        mgmt.set_gen_osc_loop(loops);
+
+        match result {
+            Ok((expected, captured)) => {
+                let max_error = compare(&expected, gen.board().dac_bits(),
+                                        &captured, osc.board().adc_bits());
+                LoopReport {
+                    channel,
+                    max_error: Some(max_error),
+                    error: None,
+                    passed: max_error <= self.tolerance,
+                }
+            }
+            Err(err) => LoopReport { channel, max_error: None, error: Some(err), passed: false },
+        }
+    }
+
+    /// Output the test waveform and capture it.
+    fn capture(&self, gen: &mut Generator, osc: &mut Oscilloscope) -> Result<(Vec<i16>, Vec<i16>)> {
+        let max = ((1 << (gen.board().dac_bits() - 1)) - 1) as f32;
+        let waveform: Vec<i16> = (0..PERIOD)
+            .map(|i| ((2.0 * PI * i as f32 / PERIOD as f32).sin() * AMPLITUDE * max).round() as i16)
+            .collect();
+
+        gen.default();
+        gen.set_waveform_raw(&waveform)?;
+        gen.set_amplitude(1.0);
+        gen.set_offset(0.0);
+        gen.set_frequency(gen.board().sampling_frequency() as f32 / PERIOD as f32);
+
+        osc.default();
+        osc.set_decimation(1);
+        osc.set_filter_bypass(true);
+        osc.set_trigger_pre(self.num_samples as u32);
+        osc.set_trigger_post(0);
+
+        gen.reset();
+        osc.reset();
+        gen.start_trigger();
+        osc.start();
+        let start = Instant::now();
+        while osc.available() < self.num_samples {
+            if start.elapsed() > self.timeout {
+                osc.stop();
+                return Err(Error::Timeout(self.timeout));
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        osc.stop();
+        Ok((waveform, osc.data(self.num_samples)))
+    }
+}
+
+impl ::std::default::Default for SelfTest {
+    fn default() -> Self {
+        SelfTest::new()
+    }
+}
+
+/// Largest deviation of `captured` from the periodic `expected` waveform
+/// at the best alignment, relative to full scale.
+///
+/// `expected` holds `expected_bits` wide generator samples and `captured`
+/// `captured_bits` wide oscilloscope samples, both are scaled to their full
+/// scale before comparing them.
+pub fn compare(expected: &[i16], expected_bits: u32, captured: &[i16], captured_bits: u32) -> f32 {
+    if expected.is_empty() || captured.is_empty() {
+        return f32::INFINITY;
+    }
+    let expected = normalize(expected, expected_bits);
+    let captured = normalize(captured, captured_bits);
+    (0..expected.len())
+        .map(|shift| {
+            captured.iter().enumerate()
+                .map(|(i, sample)| (sample - expected[(i + shift) % expected.len()]).abs())
+                .fold(0.0, f32::max)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Scale `bits` wide samples to full scale.
+fn normalize(samples: &[i16], bits: u32) -> Vec<f32> {
+    let full_scale = (1 << (bits - 1)) as f32;
+    samples.iter().map(|sample| *sample as f32 / full_scale).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use fake::{self, FakeMemory};
+    use gen;
+    use osc;
+    use uio::{BufferDevice, RegDevice};
+
+    /// One period of the test waveform with `bits` wide samples.
+    fn sine(bits: u32) -> Vec<i16> {
+        let max = ((1 << (bits - 1)) - 1) as f32;
+        (0..PERIOD)
+            .map(|i| ((2.0 * PI * i as f32 / PERIOD as f32).sin() * AMPLITUDE * max).round() as i16)
+            .collect()
+    }
+
+    #[test]
+    fn compare_shifted() {
+        let expected = sine(14);
+        let captured: Vec<i16> = (0..2 * PERIOD).map(|i| expected[(i + 300) % PERIOD]).collect();
+        assert_eq!(compare(&expected, 14, &captured, 14), 0.0);
+        // The same waveform captured with a wider ADC.
+        let captured: Vec<i16> = captured.iter().map(|sample| sample * 4).collect();
+        assert_eq!(compare(&expected, 14, &captured, 16), 0.0);
+    }
+
+    #[test]
+    fn compare_error() {
+        let expected = sine(14);
+        let captured: Vec<i16> = expected.iter().map(|sample| sample + 205).collect();
+        let max_error = compare(&expected, 14, &captured, 14);
+        assert!((max_error - 205.0 / 8192.0).abs() < 1e-6);
+        assert!(max_error > SelfTest::new().tolerance);
+    }
+
+    #[test]
+    fn compare_empty() {
+        assert_eq!(compare(&sine(14), 14, &[], 14), f32::INFINITY);
+        assert_eq!(compare(&[], 14, &sine(14), 14), f32::INFINITY);
+    }
+
+    #[test]
+    fn test_loop_restores_loops() {
+        let mut mgmt = Management::from_mapping(Box::new(FakeMemory::new(8))).unwrap();
+        let mut gen = Generator::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                               Box::new(FakeMemory::new(gen::BUFFER_SIZE as usize * 2))).unwrap();
+        let mut osc = Oscilloscope::from_mappings(Box::new(FakeMemory::new(0x100)),
+                                                  Box::new(FakeMemory::new(osc::BUFFER_SIZE as usize * 2))).unwrap();
+        mgmt.set_gen_osc_loop(0b10);
+
+        // Fill the buffer with whole periods, so the capture matches the
+        // generated waveform at some alignment.
+        let waveform = sine(14);
+        for i in 0..osc::BUFFER_SIZE as usize {
+            fake::write_u16(osc.buffer_mmap(), i * 2, waveform[i % PERIOD] as u16 & 0x3fff);
+        }
+        fake::write(osc.reg_mmap(), 0x18, osc::BUFFER_SIZE);
+        let mut test = SelfTest::new();
+        let report = test.test_loop(&mut mgmt, &mut gen, &mut osc, 0);
+        assert!(report.passed, "{}", report);
+        assert_eq!(mgmt.gen_osc_loop(), 0b10);
+
+        // A capture timing out restores the loops as well.
+        fake::write(osc.reg_mmap(), 0x18, 0);
+        test.set_timeout(Duration::from_millis(10));
+        let report = test.test_loop(&mut mgmt, &mut gen, &mut osc, 1);
+        match report.error {
+            Some(Error::Timeout(_)) => assert!(!report.passed),
+            _ => panic!("capture should time out"),
+        }
+        assert_eq!(mgmt.gen_osc_loop(), 0b10);
+    }
+
+    #[test]
+    fn report_display() {
+        let report = Report {
+            loops: vec![
+                LoopReport { channel: 0, max_error: Some(0.0015), error: None, passed: true },
+                LoopReport {
+                    channel: 1,
+                    max_error: None,
+                    error: Some(Error::Timeout(Duration::from_secs(1))),
+                    passed: false,
+                },
+            ],
+        };
+        let timeout = Error::Timeout(Duration::from_secs(1)).to_string();
+        assert_eq!(report.to_string(),
+                   format!("gen0 -> osc0: pass (max error 0.0015)\ngen1 -> osc1: FAIL ({})", timeout));
+    }
+}
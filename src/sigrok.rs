@@ -0,0 +1,237 @@
+use std::io::{self, Write};
+use error::Result;
+use la::LogicAnalyzer;
+use la_channels::{Channels, CHANNELS};
+use la_mask::LaMaskRegsAPI;
+use la_rle::{self, Run};
+
+/// Bytes per sample in the logic data chunks.
+const UNIT_SIZE: usize = 2;
+
+/// sigrok session (`.sr`) writer for logic analyzer captures.
+///
+/// The session can be opened with PulseView or `sigrok-cli -i`.
+pub struct Session {
+    channels: Channels,
+    sample_rate: f32,
+}
+
+impl Session {
+    /// Create a writer for channels in `mask` sampled at `sample_rate` Hz.
+    pub fn new(mask: u32, sample_rate: f32) -> Self {
+        Session {
+            channels: Channels::new(mask, "D"),
+            sample_rate,
+        }
+    }
+
+    /// Create a writer using the input mask and sample rate of `la`.
+    pub fn from_la(la: &LogicAnalyzer) -> Self {
+        Session::new(la.input_mask(), la.sample_rate())
+    }
+
+    /// Set the probe name of `channel`.
+    ///
+    /// Fails with `Error::InvalidChannel` if `channel` is out of range.
+    pub fn set_channel_name(&mut self, channel: usize, name: &str) -> Result<()> {
+        self.channels.set_name(channel, name)
+    }
+
+    fn metadata(&self) -> String {
+        let mut metadata = format!("[global]\n\
+                                    sigrok version=0.5.0\n\
+                                    \n\
+                                    [device 1]\n\
+                                    capturefile=logic-1\n\
+                                    total probes={}\n\
+                                    samplerate={} Hz\n\
+                                    total analog=0\n",
+                                   CHANNELS, self.sample_rate.round() as u64);
+        for (ch, name) in self.channels.iter() {
+            metadata.push_str(&format!("probe{}={}\n", ch + 1, name));
+        }
+        metadata.push_str(&format!("unitsize={}\n", UNIT_SIZE));
+        metadata
+    }
+
+    /// Write a capture of plain samples.
+    pub fn write<W: Write>(&self, w: &mut W, samples: &[u16]) -> io::Result<()> {
+        let mut logic = Vec::with_capacity(samples.len() * UNIT_SIZE);
+        for sample in samples {
+            logic.push(*sample as u8);
+            logic.push((*sample >> 8) as u8);
+        }
+        let mut zip = ZipWriter::new(w);
+        zip.file("version", b"2")?;
+        zip.file("metadata", self.metadata().as_bytes())?;
+        zip.file("logic-1-1", &logic)?;
+        zip.finish()
+    }
+
+    /// Write a capture given as runs of identical samples.
+    pub fn write_runs<W: Write>(&self, w: &mut W, runs: &[Run]) -> io::Result<()> {
+        let samples: Vec<u16> = la_rle::expand(runs).map(|(_, value)| value).collect();
+        self.write(w, &samples)
+    }
+}
+
+/// Minimal zip archive writer storing files uncompressed.
+struct ZipWriter<'a, W: Write + 'a> {
+    w: &'a mut W,
+    offset: u32,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+/// DOS date of 1980-01-01.
+const DOS_DATE: u16 = 0x21;
+
+impl<'a, W: Write> ZipWriter<'a, W> {
+    fn new(w: &'a mut W) -> Self {
+        ZipWriter { w, offset: 0, central: Vec::new(), entries: 0 }
+    }
+
+    fn file(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let crc = crc32(data);
+        let mut header = Vec::new();
+        put_u32(&mut header, 0x04034b50);
+        put_u16(&mut header, 20);
+        put_u16(&mut header, 0);
+        put_u16(&mut header, 0);
+        put_u16(&mut header, 0);
+        put_u16(&mut header, DOS_DATE);
+        put_u32(&mut header, crc);
+        put_u32(&mut header, data.len() as u32);
+        put_u32(&mut header, data.len() as u32);
+        put_u16(&mut header, name.len() as u16);
+        put_u16(&mut header, 0);
+        header.extend_from_slice(name.as_bytes());
+        self.w.write_all(&header)?;
+        self.w.write_all(data)?;
+
+        let central = &mut self.central;
+        put_u32(central, 0x02014b50);
+        put_u16(central, 20);
+        put_u16(central, 20);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u16(central, DOS_DATE);
+        put_u32(central, crc);
+        put_u32(central, data.len() as u32);
+        put_u32(central, data.len() as u32);
+        put_u16(central, name.len() as u16);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u32(central, 0);
+        put_u32(central, self.offset);
+        central.extend_from_slice(name.as_bytes());
+
+        self.offset += (header.len() + data.len()) as u32;
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut end = Vec::new();
+        put_u32(&mut end, 0x06054b50);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, self.entries);
+        put_u16(&mut end, self.entries);
+        put_u32(&mut end, self.central.len() as u32);
+        put_u32(&mut end, self.offset);
+        put_u16(&mut end, 0);
+        self.w.write_all(&self.central)?;
+        self.w.write_all(&end)
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.push(value as u8);
+    buf.push((value >> 8) as u8);
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    put_u16(buf, value as u16);
+    put_u16(buf, (value >> 16) as u16);
+}
+
+/// CRC-32 (IEEE 802.3) as used by zip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(buf: &[u8], pos: usize) -> usize {
+        buf[pos] as usize | (buf[pos + 1] as usize) << 8
+    }
+
+    fn u32_at(buf: &[u8], pos: usize) -> u32 {
+        u16_at(buf, pos) as u32 | (u16_at(buf, pos + 2) as u32) << 16
+    }
+
+    /// Read the files of an archive through its central directory.
+    fn unzip(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), 0x06054b50);
+        let entries = u16_at(archive, end + 10);
+        let mut pos = u32_at(archive, end + 16) as usize;
+        assert_eq!(pos + u32_at(archive, end + 12) as usize, end);
+        let mut files = Vec::new();
+        for _ in 0..entries {
+            assert_eq!(u32_at(archive, pos), 0x02014b50);
+            let crc = u32_at(archive, pos + 16);
+            let size = u32_at(archive, pos + 24) as usize;
+            let name_len = u16_at(archive, pos + 28);
+            let name = &archive[pos + 46..pos + 46 + name_len];
+            let local = u32_at(archive, pos + 42) as usize;
+            assert_eq!(u32_at(archive, local), 0x04034b50);
+            assert_eq!(u32_at(archive, local + 14), crc);
+            assert_eq!(u32_at(archive, local + 18) as usize, size);
+            assert_eq!(&archive[local + 30..local + 30 + name_len], name);
+            let data = &archive[local + 30 + name_len..local + 30 + name_len + size];
+            assert_eq!(crc32(data), crc);
+            files.push((String::from_utf8(name.to_vec()).unwrap(), data.to_vec()));
+            pos += 46 + name_len;
+        }
+        files
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414fa339);
+    }
+
+    #[test]
+    fn session_archive() {
+        let mut session = Session::new(0b101, 12500.0);
+        session.set_channel_name(2, "tx").unwrap();
+        assert!(session.set_channel_name(16, "rx").is_err());
+        let mut archive = Vec::new();
+        session.write(&mut archive, &[0, 1, 4, 0x1234]).unwrap();
+
+        let files = unzip(&archive);
+        let names: Vec<_> = files.iter().map(|file| &*file.0).collect();
+        assert_eq!(names, ["version", "metadata", "logic-1-1"]);
+        assert_eq!(files[0].1, b"2");
+        let metadata = String::from_utf8(files[1].1.clone()).unwrap();
+        assert!(metadata.contains("samplerate=12500 Hz\n"));
+        assert!(metadata.contains("probe1=D0\nprobe3=tx\nunitsize=2\n"));
+        assert_eq!(files[2].1, [0, 0, 1, 0, 4, 0, 0x34, 0x12]);
+    }
+}
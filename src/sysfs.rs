@@ -0,0 +1,229 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use compat::CompatTable;
+use error::{Error, Result};
+
+/// Default location of the UIO class in sysfs.
+pub const SYSFS_ROOT: &str = "/sys/class/uio";
+/// Default directory of the UIO device nodes.
+pub const DEV_DIR: &str = "/dev";
+
+/// Names of the UIO devices provided by the mercury firmware.
+pub const MERCURY_DEVICES: [&str; 9] = [
+    "hwid", "ps2pl", "mgmt", "gen0", "gen1", "osc0", "osc1", "lg", "la",
+];
+
+/// Memory mapping of a UIO device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UioMap {
+    /// Physical address of the mapping.
+    pub addr: u64,
+    /// Size of the mapping in bytes.
+    pub size: usize,
+}
+
+/// UIO device found in sysfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UioInfo {
+    /// Number of the device, `/dev/uioN`.
+    pub num: usize,
+    /// Name of the device as given by the device tree.
+    pub name: String,
+    /// Memory mappings ordered by index.
+    pub maps: Vec<UioMap>,
+}
+
+/// UIO devices listed in sysfs and their device nodes.
+///
+/// Device constructors taking a `Sysfs` find, lock and map their device
+/// through it, so they can be pointed at a different tree. They check the
+/// loaded bitstream against its `CompatTable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sysfs {
+    root: PathBuf,
+    dev: PathBuf,
+    compat: CompatTable,
+}
+
+impl Sysfs {
+    /// Scan `/sys/class/uio`, with the device nodes in `/dev`.
+    pub fn new() -> Self {
+        Sysfs::with_root(SYSFS_ROOT)
+    }
+
+    /// Scan a different directory laid out like `/sys/class/uio`.
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        Sysfs { root: root.into(), dev: PathBuf::from(DEV_DIR), compat: CompatTable::new() }
+    }
+
+    /// Directory scanned for UIO devices.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Set the directory holding the `uioN` device nodes.
+    pub fn set_dev_dir<P: Into<PathBuf>>(&mut self, dev: P) {
+        self.dev = dev.into();
+    }
+
+    /// Compatibility table device constructors check the bitstream against.
+    pub fn compat(&self) -> &CompatTable {
+        &self.compat
+    }
+
+    /// Set the compatibility table and policy, see `compat::check`.
+    pub fn set_compat(&mut self, compat: CompatTable) {
+        self.compat = compat;
+    }
+
+    /// Path of the device node `uioN`.
+    pub fn dev_path(&self, num: usize) -> PathBuf {
+        self.dev.join(format!("uio{}", num))
+    }
+
+    /// List all UIO devices ordered by number.
+    pub fn devices(&self) -> Result<Vec<UioInfo>> {
+        let mut devices = Vec::new();
+        for entry in read_dir(&self.root)? {
+            if let Some(num) = index(&entry, "uio") {
+                devices.push(self.device(num)?);
+            }
+        }
+        devices.sort_by_key(|info| info.num);
+        Ok(devices)
+    }
+
+    /// List the UIO devices of the mercury firmware.
+    pub fn mercury_devices(&self) -> Result<Vec<UioInfo>> {
+        let mut devices = self.devices()?;
+        devices.retain(|info| MERCURY_DEVICES.contains(&&*info.name));
+        Ok(devices)
+    }
+
+    /// Read the description of `/dev/uioN`.
+    pub fn device(&self, num: usize) -> Result<UioInfo> {
+        let path = self.root.join(format!("uio{}", num));
+        let name = read_attr(&path.join("name"))?;
+        let mut maps = Vec::new();
+        let maps_path = path.join("maps");
+        if maps_path.is_dir() {
+            let mut nums: Vec<usize> = read_dir(&maps_path)?.iter()
+                .filter_map(|entry| index(entry, "map"))
+                .collect();
+            nums.sort();
+            for map in nums {
+                let map_path = maps_path.join(format!("map{}", map));
+                let addr = read_hex(&map_path.join("addr"))?;
+                let size = read_hex(&map_path.join("size"))? as usize;
+                maps.push(UioMap { addr, size });
+            }
+        }
+        Ok(UioInfo { num, name, maps })
+    }
+
+    /// Find a UIO device by name.
+    ///
+    /// Uses the `uio/<name>` symlink created by udev in the device node
+    /// directory if it exists and falls back to scanning all devices.
+    pub fn find(&self, name: &str) -> Result<UioInfo> {
+        let link = self.dev.join("uio").join(name);
+        if fs::symlink_metadata(&link).is_ok() {
+            return self.device(::uio::get_uio_num(&link)?);
+        }
+        self.devices()?
+            .into_iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| Error::UioNotFound(name.to_string()))
+    }
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Sysfs::new()
+    }
+}
+
+fn read_dir(path: &Path) -> Result<Vec<String>> {
+    let entries = fs::read_dir(path)
+        .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+        .map_err(|err| Error::Sysfs(path.to_path_buf(), err))?;
+    Ok(entries.iter()
+       .filter_map(|entry| entry.file_name().into_string().ok())
+       .collect())
+}
+
+fn index(entry: &str, prefix: &str) -> Option<usize> {
+    entry.strip_prefix(prefix).and_then(|num| num.parse().ok())
+}
+
+fn read_attr(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(value) => Ok(value.trim().to_string()),
+        Err(err) => Err(Error::Sysfs(path.to_path_buf(), err)),
+    }
+}
+
+fn read_hex(path: &Path) -> Result<u64> {
+    let value = read_attr(path)?;
+    let digits = value.strip_prefix("0x").unwrap_or(&value);
+    u64::from_str_radix(digits, 16).map_err(|_| {
+        let err = io::Error::new(io::ErrorKind::InvalidData, format!("invalid number {:?}", value));
+        Error::Sysfs(path.to_path_buf(), err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::os::unix::fs::symlink;
+    use fake::FakeSysfs;
+    use hwid::HwId;
+
+    #[test]
+    fn devices_sorted_by_number() {
+        let fake = FakeSysfs::new();
+        fake.add_device(10, "la", &[0x1000, 0x8000]);
+        fake.add_device(2, "hwid", &[0x1000]);
+        fake.add_device(1, "other", &[]);
+        let nums: Vec<usize> = fake.sysfs().devices().unwrap().iter().map(|info| info.num).collect();
+        assert_eq!(nums, vec![1, 2, 10]);
+        let names: Vec<String> = fake.sysfs().mercury_devices().unwrap()
+            .into_iter().map(|info| info.name).collect();
+        assert_eq!(names, vec!["hwid", "la"]);
+    }
+
+    #[test]
+    fn device_maps() {
+        let fake = FakeSysfs::new();
+        fake.add_device(10, "la", &[0x1000, 0x8000]);
+        fake.add_device(3, "ps2pl", &[]);
+        let la = fake.sysfs().find("la").unwrap();
+        assert_eq!(la.num, 10);
+        assert_eq!(la.maps, vec![
+            UioMap { addr: 0x4000_0000, size: 0x1000 },
+            UioMap { addr: 0x4010_0000, size: 0x8000 },
+        ]);
+        assert_eq!(fake.sysfs().device(3).unwrap().maps, vec![]);
+        fs::create_dir(fake.path().join("dev/uio")).unwrap();
+        symlink("../uio3", fake.path().join("dev/uio/la")).unwrap();
+        assert_eq!(fake.sysfs().find("la").unwrap().num, 3);
+        match fake.sysfs().find("gen0") {
+            Err(Error::UioNotFound(ref name)) if name == "gen0" => {}
+            _ => panic!("missing device should not be found"),
+        }
+    }
+
+    #[test]
+    fn open_hwid() {
+        let fake = FakeSysfs::new();
+        fake.add_device(12, "hwid", &[0x1000]);
+        OpenOptions::new().write(true).open(fake.sysfs().dev_path(12))
+            .and_then(|mut file| file.write_all(&[0x21, 0x43, 0x65, 0x87]))
+            .unwrap();
+        let hwid = HwId::with_sysfs(&fake.sysfs()).unwrap();
+        assert_eq!(hwid.hwid(), 0x8765_4321);
+    }
+}
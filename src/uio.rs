@@ -1,13 +1,105 @@
-use std::fs;
+use std::fs::{self, OpenOptions};
 use std::mem;
-use std::path::PathBuf;
-pub use _uio::{UioDevice, MemoryMap};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use error::{Error, Result};
+use libc;
+use sysfs::{Sysfs, UioInfo};
 
-pub fn get_uio_num(path: &str) -> usize {
-    let path = fs::read_link(PathBuf::from(path)).unwrap();
-    let string = path.to_str().unwrap();
-    let substring = &string[6..];
-    substring.parse::<usize>().unwrap()
+/// Get the number of the UIO device a `/dev/uio/*` symlink points to.
+pub fn get_uio_num<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let path = path.as_ref().to_path_buf();
+    let target = match fs::read_link(&path) {
+        Ok(target) => target,
+        Err(err) => return Err(Error::DeviceNotFound(path, err)),
+    };
+    let num = target.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("uio"))
+        .and_then(|num| num.parse::<usize>().ok());
+    match num {
+        Some(num) => Ok(num),
+        None => Err(Error::InvalidSymlink(path)),
+    }
+}
+
+/// Find a UIO device by name, see `Sysfs::find`.
+pub fn open_uio(sysfs: &Sysfs, name: &str) -> Result<UioInfo> {
+    sysfs.find(name)
+}
+
+/// Map memory mapping `mapping` of a UIO device.
+///
+/// UIO selects the mapping by the page offset passed to `mmap`, the size
+/// is taken from sysfs.
+pub fn map_mapping(sysfs: &Sysfs, uio: &UioInfo, mapping: usize) -> Result<Box<dyn Mapping>> {
+    let len = match uio.maps.get(mapping) {
+        Some(map) if map.size > 0 => map.size,
+        _ => return Err(Error::Mmap(uio.num, mapping)),
+    };
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(sysfs.dev_path(uio.num))
+        .map_err(|err| Error::Open(uio.num, err))?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let data = unsafe {
+        libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED,
+                   file.as_raw_fd(), (mapping * page_size) as libc::off_t)
+    };
+    if data == libc::MAP_FAILED {
+        return Err(Error::Mmap(uio.num, mapping));
+    }
+    Ok(Box::new(UioMapping { data: data as *mut u8, len }))
+}
+
+/// Check that `mapping` is large enough to hold a `T`.
+pub fn check_size<T>(mapping: &dyn Mapping) -> Result<()> {
+    let size = mem::size_of::<T>();
+    if mapping.len() < size {
+        return Err(Error::MappingTooSmall(mapping.len(), size));
+    }
+    Ok(())
+}
+
+/// Memory backing the registers or buffer of a device.
+///
+/// Mappings are `Send` so a device can be moved to another thread, but
+/// not `Sync`, so a device can't be accessed from several threads at once.
+pub trait Mapping: Send {
+    /// Pointer to the start of the mapping.
+    fn data(&self) -> *mut u8;
+    /// Size of the mapping in bytes.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Memory map of a UIO device, unmapped when dropped.
+struct UioMapping {
+    data: *mut u8,
+    len: usize,
+}
+
+// The memory map is only accessed through the device owning it.
+unsafe impl Send for UioMapping {}
+
+impl Mapping for UioMapping {
+    fn data(&self) -> *mut u8 {
+        self.data
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Drop for UioMapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.data as *mut libc::c_void, self.len); }
+    }
 }
 
 pub trait Default {
@@ -20,39 +112,53 @@ pub trait Show {
     fn show(&self);
 }
 
-pub trait RegDevice {
+/// Device with memory mapped registers.
+///
+/// Mutable access is only handed out through `&mut self`.
+///
+/// # Safety
+///
+/// Implementors must own the mapping exclusively and ensure it is at least
+/// `size_of::<Registers>()` bytes large, see `check_size`.
+pub unsafe trait RegDevice {
     type Registers;
 
-    fn reg_mmap(&self) -> &MemoryMap;
+    fn reg_mmap(&self) -> &dyn Mapping;
 
     fn regs(&self) -> &Self::Registers {
-        unsafe { mem::transmute::<*mut u8, &Self::Registers>(self.reg_mmap().data()) }
+        unsafe { &*(self.reg_mmap().data() as *const Self::Registers) }
     }
 
-    fn mut_regs(&self) -> &mut Self::Registers {
-        unsafe { mem::transmute::<*mut u8, &mut Self::Registers>(self.reg_mmap().data()) }
+    fn mut_regs(&mut self) -> &mut Self::Registers {
+        unsafe { &mut *(self.reg_mmap().data() as *mut Self::Registers) }
     }
 }
 
-pub trait BufferDevice {
+/// Device with a memory mapped buffer.
+///
+/// # Safety
+///
+/// Implementors must own the mapping exclusively and ensure it is at least
+/// `size_of::<Buffer>()` bytes large, see `check_size`.
+pub unsafe trait BufferDevice {
     type Buffer;
 
-    fn buffer_mmap(&self) -> &MemoryMap;
+    fn buffer_mmap(&self) -> &dyn Mapping;
 
     fn buffer(&self) -> &Self::Buffer {
-        unsafe { mem::transmute::<*mut u8, &Self::Buffer>(self.buffer_mmap().data()) }
+        unsafe { &*(self.buffer_mmap().data() as *const Self::Buffer) }
     }
 
-    fn mut_buffer(&self) -> &mut Self::Buffer {
-        unsafe { mem::transmute::<*mut u8, &mut Self::Buffer>(self.buffer_mmap().data()) }
+    fn mut_buffer(&mut self) -> &mut Self::Buffer {
+        unsafe { &mut *(self.buffer_mmap().data() as *mut Self::Buffer) }
     }
 }
 
 pub trait IrqAPI {
     /// Enable interrupt
-    fn enable_irq(&mut self);
+    fn enable_irq(&mut self) -> Result<()>;
     /// Disable interrupt
-    fn disable_irq(&mut self);
+    fn disable_irq(&mut self) -> Result<()>;
     /// Wait for interrupt
-    fn wait_irq(&mut self) -> u32;
+    fn wait_irq(&mut self) -> Result<u32>;
 }
@@ -0,0 +1,164 @@
+use std::io::{self, Write};
+use error::Result;
+use la::LogicAnalyzer;
+use la_channels::Channels;
+use la_mask::LaMaskRegsAPI;
+use la_rle::{self, Run};
+
+/// VCD identifier code of channel 0, the following channels use the next letters.
+const ID_BASE: u8 = b'a';
+/// VCD identifier code of the trigger wire.
+const TRIGGER_ID: char = 'T';
+
+/// Value Change Dump writer for logic analyzer captures.
+///
+/// Emits one wire per channel enabled in the input mask.
+pub struct Vcd {
+    channels: Channels,
+    sample_period: f32,
+    trigger: Option<u64>,
+}
+
+impl Vcd {
+    /// Create a writer for channels in `mask` sampled every `sample_period` seconds.
+    pub fn new(mask: u32, sample_period: f32) -> Self {
+        Vcd {
+            channels: Channels::new(mask, "ch"),
+            sample_period,
+            trigger: None,
+        }
+    }
+
+    /// Create a writer using the input mask and sample period of `la`.
+    pub fn from_la(la: &LogicAnalyzer) -> Self {
+        Vcd::new(la.input_mask(), la.sample_period())
+    }
+
+    /// Set the name of the wire of `channel`.
+    ///
+    /// Fails with `Error::InvalidChannel` if `channel` is out of range.
+    pub fn set_channel_name(&mut self, channel: usize, name: &str) -> Result<()> {
+        self.channels.set_name(channel, &name.replace(' ', "_"))
+    }
+
+    /// Mark the sample with index `trigger` on a separate `trigger` wire.
+    pub fn set_trigger(&mut self, trigger: Option<u64>) {
+        self.trigger = trigger;
+    }
+
+    /// Sample index to time in nanoseconds.
+    fn time(&self, index: u64) -> u64 {
+        (index as f64 * self.sample_period as f64 * 1e9).round() as u64
+    }
+
+    /// Write a capture of plain samples.
+    pub fn write_samples<W: Write>(&self, w: &mut W, samples: &[u16]) -> io::Result<()> {
+        self.write(w, &la_rle::from_samples(samples))
+    }
+
+    /// Write a capture given as runs of identical samples.
+    pub fn write<W: Write>(&self, w: &mut W, runs: &[Run]) -> io::Result<()> {
+        let channels: Vec<usize> = self.channels.iter().map(|(ch, _)| ch).collect();
+
+        writeln!(w, "$version redpitaya-mercury $end")?;
+        writeln!(w, "$timescale 1 ns $end")?;
+        writeln!(w, "$scope module la $end")?;
+        for (ch, name) in self.channels.iter() {
+            writeln!(w, "$var wire 1 {} {} $end", (ID_BASE + ch as u8) as char, name)?;
+        }
+        if self.trigger.is_some() {
+            writeln!(w, "$var wire 1 {} trigger $end", TRIGGER_ID)?;
+        }
+        writeln!(w, "$upscope $end")?;
+        writeln!(w, "$enddefinitions $end")?;
+
+        let mut trigger = self.trigger;
+        let mut last: Option<u16> = None;
+        for run in runs {
+            if let Some(t) = trigger {
+                if t < run.start {
+                    writeln!(w, "#{}", self.time(t))?;
+                    writeln!(w, "1{}", TRIGGER_ID)?;
+                    trigger = None;
+                }
+            }
+            let fire = trigger == Some(run.start);
+            match last {
+                None => {
+                    writeln!(w, "#{}", self.time(run.start))?;
+                    writeln!(w, "$dumpvars")?;
+                    for ch in &channels {
+                        writeln!(w, "{}{}", (run.value >> ch) & 1, (ID_BASE + *ch as u8) as char)?;
+                    }
+                    if self.trigger.is_some() {
+                        writeln!(w, "0{}", TRIGGER_ID)?;
+                    }
+                    writeln!(w, "$end")?;
+                }
+                Some(prev) => {
+                    let changed: Vec<_> = channels.iter()
+                        .filter(|ch| (prev ^ run.value) & (1 << **ch) != 0)
+                        .collect();
+                    if !changed.is_empty() || fire {
+                        writeln!(w, "#{}", self.time(run.start))?;
+                    }
+                    for ch in changed {
+                        writeln!(w, "{}{}", (run.value >> ch) & 1, (ID_BASE + *ch as u8) as char)?;
+                    }
+                }
+            }
+            if fire {
+                writeln!(w, "1{}", TRIGGER_ID)?;
+                trigger = None;
+            }
+            last = Some(run.value);
+        }
+        if let Some(run) = runs.last() {
+            let end = run.start + run.length;
+            if let Some(t) = trigger {
+                if t < end {
+                    writeln!(w, "#{}", self.time(t))?;
+                    writeln!(w, "1{}", TRIGGER_ID)?;
+                }
+            }
+            writeln!(w, "#{}", self.time(end))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(vcd: &Vcd, samples: &[u16]) -> String {
+        let mut out = Vec::new();
+        vcd.write_samples(&mut out, samples).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn wires() {
+        let mut vcd = Vcd::new(0b11, 8e-9);
+        vcd.set_channel_name(1, "spi clk").unwrap();
+        assert!(vcd.set_channel_name(16, "x").is_err());
+        let out = dump(&vcd, &[0, 2, 2, 3, 7]);
+        assert!(out.contains("$var wire 1 a ch0 $end\n"));
+        assert!(out.contains("$var wire 1 b spi_clk $end\n"));
+        assert!(!out.contains("$var wire 1 c "));
+        assert!(!out.contains("trigger"));
+    }
+
+    #[test]
+    fn value_changes() {
+        let mut vcd = Vcd::new(0b11, 8e-9);
+        vcd.set_trigger(Some(2));
+        let out = dump(&vcd, &[0, 2, 2, 3, 7]);
+        assert!(out.contains("#0\n$dumpvars\n0a\n0b\n0T\n$end\n"));
+        assert!(out.contains("#8\n1b\n"));
+        assert!(out.contains("#16\n1T\n"));
+        // Only channel 2 changes at sample 4, which is not in the mask.
+        assert!(out.contains("#24\n1a\n#40\n"));
+        assert!(out.ends_with("#40\n"));
+    }
+}